@@ -1,24 +1,79 @@
 // this module does the type checking and checking if function exists
 // this runs on a syntax tree representation
 
-use crate::tree::{Ast, DynaFunction, DynaType};
+use std::collections::{HashMap, HashSet};
 
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::tree::{
+    Ast, BinaryOp, Block, DynaEnum, DynaFunction, DynaStruct, Expr, FieldInit, Literal, MatchArm,
+    Pattern, Stmt, UnaryOp, Variant, VariantFields,
+};
+use crate::types::{Primitive, Type};
+
+// every error carries the span of the call it was found in
 #[derive(Debug)]
 pub enum Error {
-    FunctionDoesntExist,
-    TypeMistach,
+    FunctionDoesntExist(Span),
+    TypeMistach(Span),
+    // assigning to a binding that wasn't declared `mut`
+    AssignToImmutable(Span),
+    StructDoesntExist(Span),
+    // a field that isn't declared on the struct, in a struct literal or a
+    // field access
+    FieldDoesntExist(Span),
+    // a field given twice in one struct literal
+    DuplicateField(Span),
+    // a struct literal without one of the declared fields and no `..base`
+    MissingField(Span),
+    // `Enum::Variant` where the enum has no such variant
+    VariantDoesntExist(Span),
+    // `break` or `continue` outside of any loop
+    BreakOutsideLoop(Span),
+    // `break 'label` or `continue 'label` with no loop of that label around
+    // it
+    LabelDoesntExist(Span),
+    // a constant index past the end of a fixed size array
+    IndexOutOfBounds(Span),
+}
+
+impl Error {
+    pub fn span(&self) -> Span {
+        match self {
+            Error::FunctionDoesntExist(span)
+            | Error::TypeMistach(span)
+            | Error::AssignToImmutable(span)
+            | Error::StructDoesntExist(span)
+            | Error::FieldDoesntExist(span)
+            | Error::DuplicateField(span)
+            | Error::MissingField(span)
+            | Error::VariantDoesntExist(span)
+            | Error::BreakOutsideLoop(span)
+            | Error::LabelDoesntExist(span)
+            | Error::IndexOutOfBounds(span) => *span,
+        }
+    }
 }
 
-pub fn type_and_fn_call_check(tree: Vec<Ast>) -> Result<(), Error> {
-    for tree_item in tree.clone() {
+pub fn type_and_fn_call_check(tree: &[Ast]) -> Result<(), Error> {
+    let items = items(tree);
+
+    for tree_item in tree {
+        if let Ast::Function(function) = tree_item {
+            Checker::new(&items, function).function()?;
+        }
+
         if let Ast::FunctionCall(call) = tree_item {
-            let name_of_function_being_called = call.fn_name;
-            let lookup = tree_lookup_function(tree.clone(), name_of_function_being_called)?;
+            let lookup = items
+                .functions
+                .get(&call.fn_name)
+                .ok_or(Error::FunctionDoesntExist(call.span))?;
 
-            let arguments: Vec<DynaType> = lookup.signature.values().cloned().collect();
+            // arguments line up with the parameters in declaration order
+            let parameters = lookup.params.iter().map(|param| &param.param_type);
 
-            if call.signature != arguments {
-                return Err(Error::TypeMistach)
+            if !call.signature.iter().eq(parameters) {
+                return Err(Error::TypeMistach(call.span));
             }
         }
     }
@@ -26,14 +81,1461 @@ pub fn type_and_fn_call_check(tree: Vec<Ast>) -> Result<(), Error> {
     Ok(())
 }
 
-fn tree_lookup_function(tree: Vec<Ast>, fn_name: String) -> Result<DynaFunction, Error> {
+// every function, struct and enum in the tree by name, the first one wins
+// if a name is declared twice
+#[derive(Default)]
+struct Items<'a, 'src> {
+    functions: HashMap<Symbol, &'a DynaFunction<'src>>,
+    structs: HashMap<Symbol, &'a DynaStruct>,
+    enums: HashMap<Symbol, &'a DynaEnum>,
+}
+
+// checks the body of one function
+struct Checker<'a, 'src> {
+    items: &'a Items<'a, 'src>,
+    function: &'a DynaFunction<'src>,
+    // the bindings in scope, innermost block last, the parameters are the
+    // outermost scope
+    scopes: Vec<HashMap<Symbol, Local>>,
+    // the loops around the expression being checked, innermost last
+    loops: Vec<Loop>,
+}
+
+// a loop `break` and `continue` can jump out of
+struct Loop {
+    label: Option<Symbol>,
+    // only `loop` can `break` with a value, `while` and `for` can stop
+    // without one
+    takes_value: bool,
+    // the type every `break` value has to be, unknown until the first one
+    break_type: Option<Type>,
+    broken: bool,
+}
+
+// the type of a struct or struct-like variant and its fields by name
+type Fields<'a> = (Type, HashMap<Symbol, &'a Type>);
+
+// a parameter or `let` binding
+struct Local {
+    local_type: Option<Type>,
+    mutable: bool,
+}
+
+impl<'a, 'src> Checker<'a, 'src> {
+    fn new(items: &'a Items<'a, 'src>, function: &'a DynaFunction<'src>) -> Self {
+        let params = function
+            .params
+            .iter()
+            .map(|param| {
+                let local = Local {
+                    local_type: Some(param.param_type.clone()),
+                    mutable: param.mutable,
+                };
+                (param.name, local)
+            })
+            .collect();
+
+        Checker {
+            items,
+            function,
+            scopes: vec![params],
+            loops: Vec::new(),
+        }
+    }
+
+    // the value of the body has to be the function's return type, and so
+    // does every `return` in it
+    fn function(&mut self) -> Result<(), Error> {
+        let body = &self.function.body;
+        let span = value_span(body);
+
+        match self.block(body)? {
+            Some(body_type) if !fits(&body_type, &self.function.return_type) => {
+                Err(Error::TypeMistach(span))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // type of a block's value, `None` when it can't be worked out and `!`
+    // when the block never finishes because of a `return`
+    fn block(&mut self, block: &Block) -> Result<Option<Type>, Error> {
+        self.scopes.push(HashMap::new());
+        let block_type = self.block_in_scope(block);
+        self.scopes.pop();
+
+        block_type
+    }
+
+    fn block_in_scope(&mut self, block: &Block) -> Result<Option<Type>, Error> {
+        let mut diverges = false;
+
+        for stmt in &block.stmts {
+            match stmt {
+                Stmt::Let {
+                    pattern,
+                    let_type,
+                    init,
+                    ..
+                } => self.let_stmt(pattern, let_type.as_ref(), init)?,
+                // `return`, `break` and `continue`, and anything that only
+                // ever gets to one of them like an `if` with both branches
+                // returning
+                Stmt::Expr(expr) => diverges |= self.expr(expr)? == Some(Type::Never),
+            }
+        }
+
+        match &block.tail {
+            Some(tail) => self.expr(tail),
+            None if diverges => Ok(Some(Type::Never)),
+            None => Ok(Some(Type::Unit)),
+        }
+    }
+
+    // the initializer has to fit the annotation if there is one, the
+    // binding gets the annotated type or else the initializer's
+    fn let_stmt(
+        &mut self,
+        pattern: &Pattern,
+        let_type: Option<&Type>,
+        init: &Expr,
+    ) -> Result<(), Error> {
+        let local_type = match let_type {
+            Some(let_type) => {
+                self.expect(init, let_type)?;
+                Some(let_type.clone())
+            }
+            None => self.expr(init)?,
+        };
+
+        self.bind(pattern, local_type)
+    }
+
+    // brings the names in `pattern` into scope, `value_type` is the type of
+    // the value being matched, the payloads of variants and fields of structs
+    // declared in the file get their declared types
+    fn bind(&mut self, pattern: &Pattern, value_type: Option<Type>) -> Result<(), Error> {
+        // a variant or struct pattern only matches values of its own type
+        let matches = |pattern_type: &Type, span: Span| match &value_type {
+            Some(value_type) if !fits(pattern_type, value_type) => Err(Error::TypeMistach(span)),
+            _ => Ok(()),
+        };
+
+        match pattern {
+            Pattern::Wildcard { .. } => {}
+            Pattern::Path { segments, span } => {
+                if let Some((enum_type, variant)) = self.variant(segments, *span)? {
+                    matches(&enum_type, *span)?;
+
+                    if variant.fields != VariantFields::Unit {
+                        return Err(Error::TypeMistach(*span));
+                    }
+                }
+            }
+            Pattern::Binding { name, mutable, .. } => {
+                let local = Local {
+                    local_type: value_type,
+                    mutable: *mutable,
+                };
+
+                // a binding shadows any earlier one with the same name
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(*name, local);
+                }
+            }
+            Pattern::Literal { value, span } => {
+                if value_type
+                    .is_some_and(|value_type| unify(&literal_type(value), &value_type).is_none())
+                {
+                    return Err(Error::TypeMistach(*span));
+                }
+            }
+            Pattern::TupleStruct {
+                path,
+                elements,
+                span,
+            } => {
+                let Some((enum_type, variant)) = self.variant(path, *span)? else {
+                    for element in elements {
+                        self.bind(element, None)?;
+                    }
+                    return Ok(());
+                };
+                matches(&enum_type, *span)?;
+
+                let VariantFields::Tuple(types) = &variant.fields else {
+                    return Err(Error::TypeMistach(*span));
+                };
+                if types.len() != elements.len() {
+                    return Err(Error::TypeMistach(*span));
+                }
+
+                for (element, element_type) in elements.iter().zip(types) {
+                    self.bind(element, Some(element_type.clone()))?;
+                }
+            }
+            Pattern::Struct {
+                path,
+                fields,
+                rest,
+                span,
+            } => {
+                let Some((struct_type, declared)) = self.fields(path, *span)? else {
+                    for field in fields {
+                        self.bind(&field.pattern, None)?;
+                    }
+                    return Ok(());
+                };
+                matches(&struct_type, *span)?;
+
+                for field in fields {
+                    let field_type = declared
+                        .get(&field.name)
+                        .ok_or(Error::FieldDoesntExist(field.span))?;
+                    self.bind(&field.pattern, Some((*field_type).clone()))?;
+                }
+
+                if !rest && fields.len() < declared.len() {
+                    return Err(Error::MissingField(*span));
+                }
+            }
+            Pattern::Or { alternatives, .. } => {
+                for alternative in alternatives {
+                    self.bind(alternative, value_type.clone())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // the enum type and variant `Enum::Variant` names, `None` when the path
+    // doesn't start with an enum declared in the file
+    fn variant(&self, path: &[Symbol], span: Span) -> Result<Option<(Type, &'a Variant)>, Error> {
+        let [enum_name, variant_name] = path else {
+            return Ok(None);
+        };
+        let Some(enumeration) = self.items.enums.get(enum_name).copied() else {
+            return Ok(None);
+        };
+        let variant = enumeration
+            .variant(*variant_name)
+            .ok_or(Error::VariantDoesntExist(span))?;
+        let enum_type = Type::Named(enumeration.name);
+
+        Ok(Some((enum_type, variant)))
+    }
+
+    // the type and named fields of the struct or struct-like variant `path`
+    // names, `None` when it isn't declared in the file
+    fn fields(&self, path: &[Symbol], span: Span) -> Result<Option<Fields<'a>>, Error> {
+        if let [name] = path {
+            let Some(structure) = self.items.structs.get(name).copied() else {
+                return Ok(None);
+            };
+            let struct_type = Type::Named(structure.name);
+
+            return Ok(Some((
+                struct_type,
+                structure
+                    .fields
+                    .iter()
+                    .map(|(name, ty)| (*name, ty))
+                    .collect(),
+            )));
+        }
+
+        match self.variant(path, span)? {
+            Some((enum_type, variant)) => match &variant.fields {
+                VariantFields::Struct(fields) => Ok(Some((
+                    enum_type,
+                    fields.iter().map(|(name, ty)| (*name, ty)).collect(),
+                ))),
+                _ => Err(Error::StructDoesntExist(span)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    // innermost binding called `name`
+    fn local(&self, name: Symbol) -> Option<&Local> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(&name))
+    }
+
+    // type of an expression, `None` when it isn't known yet or the
+    // expression never produces a value
+    fn expr(&mut self, expr: &Expr) -> Result<Option<Type>, Error> {
+        let expr_type = match expr {
+            Expr::Literal { value, .. } => literal_type(value),
+            Expr::Path { segments, span } => match segments[..] {
+                [name] => return Ok(self.local(name).and_then(|local| local.local_type.clone())),
+                _ => match self.variant(segments, *span)? {
+                    Some((enum_type, variant)) => match variant.fields {
+                        VariantFields::Unit => enum_type,
+                        // `Enum::Variant` on its own builds nothing until it's
+                        // called
+                        VariantFields::Tuple(_) => return Ok(None),
+                        VariantFields::Struct(_) => return Err(Error::MissingField(*span)),
+                    },
+                    None => return Ok(None),
+                },
+            },
+            Expr::Unary { op, operand, span } => {
+                let Some(operand_type) = self.expr(operand)? else {
+                    return Ok(None);
+                };
+                let allowed = match op {
+                    // unsigned integers can't be negated
+                    UnaryOp::Neg => {
+                        operand_type == Type::Never
+                            || operand_type.is_signed_integer()
+                            || operand_type.is_float()
+                            || operand_type == Type::AnyInteger
+                    }
+                    UnaryOp::Not => {
+                        operand_type == Type::Primitive(Primitive::Bool)
+                            || operand_type.is_integer()
+                    }
+                };
+
+                if !allowed {
+                    return Err(Error::TypeMistach(*span));
+                }
+
+                match (op, operand_type) {
+                    (UnaryOp::Neg, Type::AnyInteger) => Type::AnySignedInteger,
+                    (_, operand_type) => operand_type,
+                }
+            }
+            Expr::Binary { op, lhs, rhs, span } => return self.binary(*op, lhs, rhs, *span),
+            Expr::Cast {
+                value, cast_type, ..
+            } => {
+                self.expr(value)?;
+                cast_type.clone()
+            }
+            Expr::Call { callee, args, span } => return self.call(callee, args, *span),
+            // nothing is known about methods and fields yet, but what they're
+            // called on still gets checked
+            Expr::MethodCall { receiver, args, .. } => {
+                self.expr(receiver)?;
+                for arg in args {
+                    self.expr(arg)?;
+                }
+                return Ok(None);
+            }
+            Expr::Struct {
+                path,
+                fields,
+                base,
+                span,
+            } => self.struct_literal(path, fields, base.as_deref(), *span)?,
+            // fields can only be looked up on structs declared in the file,
+            // references to them are looked through
+            Expr::Field { base, field, span } => {
+                let Some(base_type) = self.expr(base)? else {
+                    return Ok(None);
+                };
+                let Type::Named(name) = deref(&base_type) else {
+                    return Ok(None);
+                };
+                let Some(structure) = self.items.structs.get(name) else {
+                    return Ok(None);
+                };
+
+                structure
+                    .field(*field)
+                    .ok_or(Error::FieldDoesntExist(*span))?
+                    .clone()
+            }
+            Expr::Index { base, index, .. } => return self.index(base, index),
+            // every element has to be of the same type
+            Expr::Array { elements, .. } => {
+                let mut element_type: Option<Type> = None;
+
+                for element in elements {
+                    let Some(found) = self.expr(element)? else {
+                        continue;
+                    };
+                    element_type = match element_type {
+                        Some(element_type) => Some(
+                            unify(&element_type, &found)
+                                .ok_or(Error::TypeMistach(element.span()))?,
+                        ),
+                        None => Some(found),
+                    };
+                }
+
+                let Some(element_type) = element_type else {
+                    return Ok(None);
+                };
+                Type::Array {
+                    element: Box::new(element_type),
+                    len: elements.len() as u128,
+                }
+            }
+            // the length has to be a `usize`, only literal lengths are known
+            Expr::Repeat { value, len, .. } => {
+                let value_type = self.expr(value)?;
+                self.expect(len, &Type::Primitive(Primitive::Usize))?;
+
+                let (
+                    Some(element_type),
+                    Expr::Literal {
+                        value: Literal::Int(len),
+                        ..
+                    },
+                ) = (value_type, &**len)
+                else {
+                    return Ok(None);
+                };
+                let Some(len) = len.value() else {
+                    return Ok(None);
+                };
+
+                Type::Array {
+                    element: Box::new(element_type),
+                    len,
+                }
+            }
+            Expr::Assign {
+                op,
+                target,
+                value,
+                span,
+            } => {
+                self.assign(*op, target, value, *span)?;
+                Type::Unit
+            }
+            Expr::Block(block) => return self.block(block),
+            Expr::If {
+                condition,
+                then_block,
+                else_branch,
+                ..
+            } => return self.if_expr(condition, then_block, else_branch.as_deref()),
+            Expr::Match {
+                scrutinee, arms, ..
+            } => return self.match_expr(scrutinee, arms),
+            Expr::While {
+                label,
+                condition,
+                body,
+                ..
+            } => {
+                self.expect(condition, &Type::Primitive(Primitive::Bool))?;
+                self.loop_body(*label, false, body)?;
+                Type::Unit
+            }
+            Expr::Loop { label, body, .. } => {
+                let Loop {
+                    break_type, broken, ..
+                } = self.loop_body(*label, true, body)?;
+
+                // a `loop` without a `break` never finishes
+                if !broken {
+                    return Ok(Some(Type::Never));
+                }
+                return Ok(break_type);
+            }
+            Expr::For {
+                label,
+                pattern,
+                iterable,
+                body,
+                ..
+            } => {
+                let element_type = self.element(iterable)?;
+
+                self.scopes.push(HashMap::new());
+                let body_type = self
+                    .bind(pattern, element_type)
+                    .and_then(|()| self.loop_body(*label, false, body));
+                self.scopes.pop();
+
+                body_type?;
+                Type::Unit
+            }
+            Expr::Break { label, value, span } => {
+                let value_type = match value {
+                    Some(value) => self.expr(value)?,
+                    None => Some(Type::Unit),
+                };
+                let target = self.loop_target(*label, *span)?;
+
+                if value.is_some() && !target.takes_value {
+                    return Err(Error::TypeMistach(*span));
+                }
+                target.broken = true;
+
+                if let Some(value_type) = value_type {
+                    target.break_type = match &target.break_type {
+                        Some(break_type) => Some(unify(break_type, &value_type).ok_or(
+                            Error::TypeMistach(value.as_deref().map_or(*span, Expr::span)),
+                        )?),
+                        None => Some(value_type),
+                    };
+                }
+
+                Type::Never
+            }
+            Expr::Continue { label, span } => {
+                self.loop_target(*label, *span)?;
+                Type::Never
+            }
+            // both ends have to be of the same type
+            Expr::Range { start, end, .. } => {
+                self.range(start.as_deref(), end.as_deref())?;
+                return Ok(None);
+            }
+            Expr::Return { value, span } => {
+                let value_type = match value {
+                    Some(value) => self.expr(value)?,
+                    None => Some(Type::Unit),
+                };
+
+                if value_type
+                    .is_some_and(|value_type| !fits(&value_type, &self.function.return_type))
+                {
+                    return Err(Error::TypeMistach(*span));
+                }
+
+                Type::Never
+            }
+        };
+
+        Ok(Some(expr_type))
+    }
+
+    // the target has to be a `mut` binding and the value has to fit it,
+    // `x op= y` follows the same rules as `x op y`
+    fn assign(
+        &mut self,
+        op: Option<BinaryOp>,
+        target: &Expr,
+        value: &Expr,
+        span: Span,
+    ) -> Result<(), Error> {
+        if let Expr::Path { segments, .. } = target {
+            if let [name] = segments[..] {
+                if self.local(name).is_some_and(|local| !local.mutable) {
+                    return Err(Error::AssignToImmutable(target.span()));
+                }
+            }
+        }
+
+        match op {
+            Some(op) => {
+                self.binary(op, target, value, span)?;
+            }
+            None => {
+                if let Some(target_type) = self.expr(target)? {
+                    self.expect(value, &target_type)?;
+                } else {
+                    self.expr(value)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // the condition has to be a `bool` and both branches have to have the
+    // same type, without an `else` the `if` has nothing to give when the
+    // condition is false so the block has to be unit
+    fn if_expr(
+        &mut self,
+        condition: &Expr,
+        then_block: &Block,
+        else_branch: Option<&Expr>,
+    ) -> Result<Option<Type>, Error> {
+        self.expect(condition, &Type::Primitive(Primitive::Bool))?;
+        let then_type = self.block(then_block)?;
+
+        let Some(else_branch) = else_branch else {
+            return match then_type {
+                Some(then_type) if !fits(&then_type, &Type::Unit) => {
+                    Err(Error::TypeMistach(value_span(then_block)))
+                }
+                _ => Ok(Some(Type::Unit)),
+            };
+        };
+        let else_type = self.expr(else_branch)?;
+
+        // a branch that never finishes takes on the other branch's type
+        match (then_type, else_type) {
+            (Some(then_type), Some(else_type)) => unify(&then_type, &else_type)
+                .ok_or(Error::TypeMistach(expr_value_span(else_branch)))
+                .map(Some),
+            (then_type, else_type) => Ok(then_type.or(else_type)),
+        }
+    }
+
+    // every arm's pattern has to fit the scrutinee, every guard has to be a
+    // `bool` and every arm has to give the same type
+    fn match_expr(&mut self, scrutinee: &Expr, arms: &[MatchArm]) -> Result<Option<Type>, Error> {
+        let scrutinee_type = self.expr(scrutinee)?;
+        let mut match_type: Option<Type> = None;
+
+        for arm in arms {
+            // the names an arm binds are only in scope in its guard and body
+            self.scopes.push(HashMap::new());
+            let arm_type = self.arm(arm, scrutinee_type.clone());
+            self.scopes.pop();
+
+            // an arm that never finishes takes on the other arms' type
+            let Some(arm_type) = arm_type? else {
+                continue;
+            };
+            match_type = match match_type {
+                Some(match_type) => Some(
+                    unify(&match_type, &arm_type)
+                        .ok_or(Error::TypeMistach(expr_value_span(&arm.body)))?,
+                ),
+                None => Some(arm_type),
+            };
+        }
+
+        Ok(match_type)
+    }
+
+    fn arm(&mut self, arm: &MatchArm, scrutinee_type: Option<Type>) -> Result<Option<Type>, Error> {
+        self.bind(&arm.pattern, scrutinee_type)?;
+
+        if let Some(guard) = &arm.guard {
+            self.expect(guard, &Type::Primitive(Primitive::Bool))?;
+        }
+
+        self.expr(&arm.body)
+    }
+
+    // every field of the struct or struct-like variant has to be given once
+    // with its declared type, fields left out are taken from `..base` which
+    // has to be of the same type
+    fn struct_literal(
+        &mut self,
+        path: &[Symbol],
+        fields: &[FieldInit],
+        base: Option<&Expr>,
+        span: Span,
+    ) -> Result<Type, Error> {
+        let (struct_type, declared) = self
+            .fields(path, span)?
+            .ok_or(Error::StructDoesntExist(span))?;
+        let mut given = HashSet::new();
+
+        for field in fields {
+            let field_type = declared
+                .get(&field.name)
+                .ok_or(Error::FieldDoesntExist(field.span))?;
+
+            if !given.insert(field.name) {
+                return Err(Error::DuplicateField(field.span));
+            }
+            self.expect(&field.value, field_type)?;
+        }
+
+        match base {
+            Some(base) => self.expect(base, &struct_type)?,
+            None if given.len() != declared.len() => return Err(Error::MissingField(span)),
+            None => {}
+        }
+
+        Ok(struct_type)
+    }
+
+    // checks the body of a loop with the loop in scope for `break` and
+    // `continue`, the body has to be unit
+    fn loop_body(
+        &mut self,
+        label: Option<Symbol>,
+        takes_value: bool,
+        body: &Block,
+    ) -> Result<Loop, Error> {
+        self.loops.push(Loop {
+            label,
+            takes_value,
+            break_type: None,
+            broken: false,
+        });
+        let body_type = self.block(body);
+        let this_loop = self.loops.pop();
+
+        match body_type? {
+            Some(body_type) if !fits(&body_type, &Type::Unit) => {
+                Err(Error::TypeMistach(value_span(body)))
+            }
+            _ => Ok(this_loop.expect("the loop was pushed above")),
+        }
+    }
+
+    // the loop a `break` or `continue` jumps out of, the innermost one or the
+    // one with its label
+    fn loop_target(&mut self, label: Option<Symbol>, span: Span) -> Result<&mut Loop, Error> {
+        let Some(label) = label else {
+            return self.loops.last_mut().ok_or(Error::BreakOutsideLoop(span));
+        };
+
+        self.loops
+            .iter_mut()
+            .rev()
+            .find(|target| target.label == Some(label))
+            .ok_or(Error::LabelDoesntExist(span))
+    }
+
+    // the type of the values a `for` loop goes through, ranges give their
+    // ends' type and arrays and slices their elements'
+    fn element(&mut self, iterable: &Expr) -> Result<Option<Type>, Error> {
+        match iterable {
+            Expr::Range { start, end, .. } => self.range(start.as_deref(), end.as_deref()),
+            _ => {
+                let iterable_type = self.expr(iterable)?;
+                Ok(iterable_type
+                    .as_ref()
+                    .and_then(elements)
+                    .map(|(element, _)| element.clone()))
+            }
+        }
+    }
+
+    // an array or slice indexed by a `usize` gives one element and by a
+    // range a slice, a constant index has to be in bounds of a fixed size
+    // array
+    fn index(&mut self, base: &Expr, index: &Expr) -> Result<Option<Type>, Error> {
+        let base_type = self.expr(base)?;
+        let Some((element, len)) = base_type.as_ref().and_then(elements) else {
+            self.expr(index)?;
+            return Ok(None);
+        };
+        let usize_type = Type::Primitive(Primitive::Usize);
+
+        if let Expr::Range { start, end, .. } = index {
+            if self
+                .range(start.as_deref(), end.as_deref())?
+                .is_some_and(|range_type| !fits(&range_type, &usize_type))
+            {
+                return Err(Error::TypeMistach(index.span()));
+            }
+
+            return Ok(Some(Type::Slice(Box::new(element.clone()))));
+        }
+
+        self.expect(index, &usize_type)?;
+
+        if let (
+            Some(len),
+            Expr::Literal {
+                value: Literal::Int(constant),
+                span,
+            },
+        ) = (len, index)
+        {
+            if constant.value().is_some_and(|constant| constant >= len) {
+                return Err(Error::IndexOutOfBounds(*span));
+            }
+        }
+
+        Ok(Some(element.clone()))
+    }
+
+    // the type of both ends of a range
+    fn range(&mut self, start: Option<&Expr>, end: Option<&Expr>) -> Result<Option<Type>, Error> {
+        let start_type = match start {
+            Some(start) => self.expr(start)?,
+            None => None,
+        };
+        let end_type = match end {
+            Some(end) => self.expr(end)?,
+            None => None,
+        };
+
+        match (start_type, end_type, end) {
+            (Some(start_type), Some(end_type), Some(end)) => unify(&start_type, &end_type)
+                .ok_or(Error::TypeMistach(end.span()))
+                .map(Some),
+            (start_type, end_type, _) => Ok(start_type.or(end_type)),
+        }
+    }
+
+    // calls to functions declared in the file have their arguments checked
+    // against the parameters in declaration order, and `Enum::Variant(args)`
+    // against the variant's payload
+    fn call(&mut self, callee: &Expr, args: &[Expr], span: Span) -> Result<Option<Type>, Error> {
+        let Expr::Path { segments, .. } = callee else {
+            for arg in args {
+                self.expr(arg)?;
+            }
+            return Ok(None);
+        };
+
+        if let Some((enum_type, variant)) = self.variant(segments, callee.span())? {
+            let VariantFields::Tuple(types) = &variant.fields else {
+                return Err(Error::TypeMistach(span));
+            };
+            if types.len() != args.len() {
+                return Err(Error::TypeMistach(span));
+            }
+
+            for (arg, arg_type) in args.iter().zip(types) {
+                self.expect(arg, arg_type)?;
+            }
+
+            return Ok(Some(enum_type));
+        }
+
+        let [name] = segments[..] else {
+            for arg in args {
+                self.expr(arg)?;
+            }
+            return Ok(None);
+        };
+
+        let function = self
+            .items
+            .functions
+            .get(&name)
+            .ok_or(Error::FunctionDoesntExist(span))?;
+
+        if function.params.len() != args.len() {
+            return Err(Error::TypeMistach(span));
+        }
+
+        // a generic parameter takes the type of the first argument passed
+        // for it on its own, generics inside other types aren't worked out
+        let mut bound = HashMap::new();
+        let unbound = |bound: &HashMap<Symbol, Type>| -> Vec<Symbol> {
+            function
+                .generics
+                .iter()
+                .copied()
+                .filter(|name| !bound.contains_key(name))
+                .collect()
+        };
+
+        for (arg, param) in args.iter().zip(&function.params) {
+            let param_type = param.param_type.substitute(&bound);
+
+            match param_type {
+                Type::Param(name) if function.generics.contains(&name) => {
+                    if let Some(arg_type) = self.expr(arg)? {
+                        bound.insert(name, arg_type);
+                    }
+                }
+                _ if param_type.has_params(&unbound(&bound)) => {
+                    self.expr(arg)?;
+                }
+                _ => self.expect(arg, &param_type)?,
+            }
+        }
+
+        let return_type = function.return_type.substitute(&bound);
+        if return_type.has_params(&unbound(&bound)) {
+            return Ok(None);
+        }
+
+        Ok(Some(return_type))
+    }
+
+    // arithmetic needs two numbers of the same type, comparisons two values
+    // of the same type and `&&` and `||` two bools
+    fn binary(
+        &mut self,
+        op: BinaryOp,
+        lhs: &Expr,
+        rhs: &Expr,
+        span: Span,
+    ) -> Result<Option<Type>, Error> {
+        let bool_type = Type::Primitive(Primitive::Bool);
+
+        if op.is_logical() {
+            self.expect(lhs, &bool_type)?;
+            self.expect(rhs, &bool_type)?;
+
+            return Ok(Some(bool_type));
+        }
+
+        let (Some(lhs_type), Some(rhs_type)) = (self.expr(lhs)?, self.expr(rhs)?) else {
+            return Ok(op.is_comparison().then_some(bool_type));
+        };
+        let operand_type = unify(&lhs_type, &rhs_type).ok_or(Error::TypeMistach(rhs.span()))?;
+
+        if op.is_comparison() {
+            Ok(Some(bool_type))
+        } else if is_number(&operand_type) || operand_type == Type::Never {
+            Ok(Some(operand_type))
+        } else {
+            Err(Error::TypeMistach(span))
+        }
+    }
+
+    // `expr` has to be of type `expected`, expressions of unknown type are
+    // let through
+    fn expect(&mut self, expr: &Expr, expected: &Type) -> Result<(), Error> {
+        match self.expr(expr)? {
+            Some(found) if !fits(&found, expected) => Err(Error::TypeMistach(expr.span())),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn is_number(ty: &Type) -> bool {
+    ty.is_integer() || ty.is_float()
+}
+
+// whether a value of type `found` can be used where `expected` is wanted
+fn fits(found: &Type, expected: &Type) -> bool {
+    match (found, expected) {
+        // a value that never exists can stand in for anything
+        (Type::Never, _) => true,
+        (Type::AnySignedInteger, _) => expected.is_signed_integer(),
+        (Type::AnyInteger, _) => expected.is_integer(),
+        (Type::AnyFloat, _) => expected.is_float(),
+        (
+            Type::Array {
+                element: found_element,
+                len: found_len,
+            },
+            Type::Array {
+                element: expected_element,
+                len: expected_len,
+            },
+        ) => found_len == expected_len && fits(found_element, expected_element),
+        (Type::Slice(found_element), Type::Slice(expected_element)) => {
+            fits(found_element, expected_element)
+        }
+        (Type::Tuple(found_elements), Type::Tuple(expected_elements)) => {
+            found_elements.len() == expected_elements.len()
+                && found_elements
+                    .iter()
+                    .zip(expected_elements)
+                    .all(|(found, expected)| fits(found, expected))
+        }
+        // a `&mut T` can be used as a `&T` but not the other way around
+        (
+            Type::Ref {
+                mutable: found_mutable,
+                referent: found_referent,
+            },
+            Type::Ref {
+                mutable: expected_mutable,
+                referent: expected_referent,
+            },
+        ) => {
+            (*found_mutable || !expected_mutable)
+                && (fits(found_referent, expected_referent)
+                    || unsizes(found_referent, expected_referent))
+        }
+        // `let bytes: &[u8] = [1, 2, 3];`
+        (
+            Type::Array { .. },
+            Type::Ref {
+                mutable: false,
+                referent,
+            },
+        ) => unsizes(found, referent),
+        _ => found == expected,
+    }
+}
+
+// whether an array of type `found` can be used as the slice `expected`
+fn unsizes(found: &Type, expected: &Type) -> bool {
+    match (found, expected) {
+        (Type::Array { element, .. }, Type::Slice(expected_element)) => {
+            fits(element, expected_element)
+        }
+        _ => false,
+    }
+}
+
+// `ty` with any references around it taken off
+fn deref(mut ty: &Type) -> &Type {
+    while let Type::Ref { referent, .. } = ty {
+        ty = referent;
+    }
+
+    ty
+}
+
+// the element type of an array or slice, or a reference to one, and the
+// length if it's a fixed size array
+fn elements(ty: &Type) -> Option<(&Type, Option<u128>)> {
+    match deref(ty) {
+        Type::Array { element, len } => Some((element, Some(*len))),
+        Type::Slice(element) => Some((element, None)),
+        _ => None,
+    }
+}
+
+// the type both operands of a binary operator end up as
+fn unify(lhs: &Type, rhs: &Type) -> Option<Type> {
+    if fits(lhs, rhs) {
+        Some(rhs.clone())
+    } else if fits(rhs, lhs) {
+        Some(lhs.clone())
+    } else {
+        None
+    }
+}
+
+// where the value of a block comes from, its tail or the whole block
+fn value_span(block: &Block) -> Span {
+    block.tail.as_ref().map_or(block.span, |tail| tail.span())
+}
+
+// like `value_span` for blocks, the span of any other expression
+fn expr_value_span(expr: &Expr) -> Span {
+    match expr {
+        Expr::Block(block) => value_span(block),
+        _ => expr.span(),
+    }
+}
+
+fn literal_type(literal: &Literal) -> Type {
+    match literal {
+        Literal::Int(integer) => integer.suffix.map_or(Type::AnyInteger, Type::named),
+        Literal::Float(float) => float.suffix.map_or(Type::AnyFloat, Type::named),
+        Literal::Str(_) => Type::Ref {
+            mutable: false,
+            referent: Box::new(Type::Primitive(Primitive::Str)),
+        },
+        Literal::Char(_) => Type::Primitive(Primitive::Char),
+        Literal::Bool(_) => Type::Primitive(Primitive::Bool),
+        Literal::Unit => Type::Unit,
+    }
+}
+
+fn items<'a, 'src>(tree: &'a [Ast<'src>]) -> Items<'a, 'src> {
+    let mut items = Items::default();
+
     for tree_item in tree {
-        if let Ast::Function(function) = tree_item {
-            if function.name == fn_name {
-                return Ok(function);
+        match tree_item {
+            Ast::Function(function) => {
+                items.functions.entry(function.name).or_insert(function);
+            }
+            Ast::Structure(structure) => {
+                items.structs.entry(structure.name).or_insert(structure);
             }
+            Ast::Enumeration(enumeration) => {
+                items.enums.entry(enumeration.name).or_insert(enumeration);
+            }
+            _ => {}
         }
     }
 
-    Err(Error::FunctionDoesntExist)
+    items
+}
+
+// parses `code`, which has to be free of syntax errors, and checks it
+#[cfg(test)]
+fn check(code: &str) -> Result<(), Error> {
+    use crate::lexer::Lexer;
+    use crate::tree::tree;
+
+    type_and_fn_call_check(&tree(Lexer::new(code)).unwrap().items)
+}
+
+#[test]
+fn test_arguments_in_declaration_order() {
+    assert!(check("fn f(a: String, b: u8, c: bool) {} f(String, u8, bool)").is_ok());
+    assert!(matches!(
+        check("fn f(a: String, b: u8) {} f(u8, String)"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f(a: String, b: u8) {} f(String)"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(check("g()"), Err(Error::FunctionDoesntExist(_))));
+}
+
+#[test]
+fn test_return_types() {
+    assert!(check("fn f() {} fn g() -> () {}").is_ok());
+    assert!(check("fn f(s: String) -> String { s }").is_ok());
+    assert!(check("fn f() -> u8 { return 1u8; }").is_ok());
+    assert!(check("fn f() -> bool { f(); true }").is_ok());
+    assert!(check("fn f() -> &str { \"x\" }").is_ok());
+    assert!(check("fn f() -> String { String::from(\"x\") }").is_ok());
+    assert!(check("fn f(b: bool) -> u8 { if b { return 1; } else { return 2; }; }").is_ok());
+    assert!(check("fn f(x: u8) -> u8 { match x { 0 => return 1, _ => return x, }; }").is_ok());
+    assert!(check("fn f() -> u8 { loop {}; }").is_ok());
+    assert!(check("fn f(x: i8) -> i8 { -return x }").is_ok());
+
+    assert!(matches!(
+        check("fn f() -> String { \"x\" }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> String { 10 }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> String {}"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f() { return 'c'; }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f(b: bool) -> u8 { return b; 1u8 }"),
+        Err(Error::TypeMistach(span)) if span == Span::new(22, 30)
+    ));
+}
+
+#[test]
+fn test_calls_in_bodies() {
+    assert!(
+        check("fn f(a: String) -> u8 { g(a, 1) } fn g(a: String, b: i32) -> u8 { 1u8 }").is_ok()
+    );
+    assert!(matches!(
+        check("fn f() { g(1) } fn g(a: String) {}"),
+        Err(Error::TypeMistach(span)) if span == Span::new(11, 12)
+    ));
+    assert!(matches!(
+        check("fn f() { { g() }; }"),
+        Err(Error::FunctionDoesntExist(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> String { g() } fn g() {}"),
+        Err(Error::TypeMistach(_))
+    ));
+}
+
+#[test]
+fn test_operators() {
+    assert!(check("fn f(x: u8) -> u8 { x * 2 + 1 }").is_ok());
+    assert!(check("fn f(x: u8, y: u8) -> bool { x < y && !(x == 1) || false }").is_ok());
+    assert!(check("fn f(x: f32) -> i64 { -x as i64 }").is_ok());
+    assert!(check("fn f() -> f64 { 1.5 * 2.0 }").is_ok());
+    assert!(check("fn f(x: i8) -> i8 { let y = -1; -x + y }").is_ok());
+
+    assert!(matches!(
+        check("fn f() -> u8 { -1u8 }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f(x: u32) -> u32 { -x }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> u8 { let y = -1; y }"),
+        Err(Error::TypeMistach(_))
+    ));
+
+    assert!(
+        matches!(check("fn f(x: u8, y: u16) -> u8 { x + y }"), Err(Error::TypeMistach(span)) if span == Span::new(32, 33))
+    );
+    assert!(matches!(
+        check("fn f(x: u8) -> u8 { x + 1.5 }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f(x: u8) -> bool { x && true }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f(x: bool) -> bool { -x }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f(x: u8) -> u8 { x < 1 }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f(s: String) -> String { s + s }"),
+        Err(Error::TypeMistach(_))
+    ));
+}
+
+#[test]
+fn test_let() {
+    assert!(check("fn f() -> u8 { let mut y = 10; y += 1; y }").is_ok());
+    assert!(check("fn f(s: String) -> u8 { let s = 1u8; { let s = \"shadowed\"; } s }").is_ok());
+    assert!(check("fn f(mut x: u8) { x = 2; }").is_ok());
+    assert!(check("fn f() { let array: &[u8] = [1, 2, 3, 4]; }").is_ok());
+
+    assert!(matches!(
+        check("fn f() { let x: String = 1; }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> String { let x = 1u8; x }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f() { let x = 1; x = 2; }"),
+        Err(Error::AssignToImmutable(span)) if span == Span::new(20, 21)
+    ));
+    assert!(matches!(
+        check("fn f(x: u8) { x += 1; }"),
+        Err(Error::AssignToImmutable(_))
+    ));
+    assert!(matches!(
+        check("fn f() { let mut x = 1u8; x = true; }"),
+        Err(Error::TypeMistach(_))
+    ));
+}
+
+#[test]
+fn test_if() {
+    assert!(check("fn f(x: u8) -> u8 { if x > 1 { x } else if x == 1 { 2 } else { 0 } }").is_ok());
+    assert!(check("fn f(b: bool) -> u8 { let x = if b { return 1; } else { 2u8 }; x }").is_ok());
+    assert!(check("fn f(b: bool) { if b { f(b); } if !b { } else { } }").is_ok());
+    assert!(check("fn f(x: i8) -> i8 { if true { f(x); } -x }").is_ok());
+
+    assert!(matches!(
+        check("fn f(x: u8) { if x { } }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f(b: bool) -> u8 { if b { 1 } else { 'c' } }"),
+        Err(Error::TypeMistach(span)) if span == Span::new(40, 43)
+    ));
+    assert!(matches!(
+        check("fn f(b: bool) -> u8 { if b { 1 } }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f(b: bool) -> String { if b { \"yes\" } else { 1 } }"),
+        Err(Error::TypeMistach(_))
+    ));
+}
+
+#[test]
+fn test_match() {
+    assert!(check("fn f(x: u8) -> u8 { match x { 0 => 1, 1 | 2 => x, n if n > 10 => return 0u8, _ => { 3 } } }").is_ok());
+    assert!(check("fn f(s: String) -> String { match s { other => other } }").is_ok());
+    assert!(check(
+        "fn f(x: u8) { match x { T::Some(s) => g(s), T::None => (), } } fn g(s: String) {}"
+    )
+    .is_ok());
+
+    assert!(matches!(
+        check("fn f(x: u8) -> u8 { match x { 0 => 1, _ => 'c' } }"),
+        Err(Error::TypeMistach(span)) if span == Span::new(43, 46)
+    ));
+    assert!(matches!(
+        check("fn f(x: u8) { match x { \"s\" => () } }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f(x: u8) { match x { n if n => () } }"),
+        Err(Error::TypeMistach(_))
+    ));
+}
+
+#[test]
+fn test_structs() {
+    let check = |code: &str| check(&format!("struct Point {{ x: u8, y: u8 }} {}", code));
+
+    assert!(check("fn f(y: u8) -> Point { Point { x: 1, y } }").is_ok());
+    assert!(check("fn f(p: Point) -> Point { Point { x: 2, ..p } }").is_ok());
+    assert!(check("fn f(p: Point) -> u8 { p.x + Point { y: 1, x: 2 }.y }").is_ok());
+
+    assert!(matches!(
+        check("fn f() -> Point { Point { x: 1 } }"),
+        Err(Error::MissingField(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> Point { Point { x: 1, y: 2, x: 3 } }"),
+        Err(Error::DuplicateField(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> Point { Point { x: 1, y: 2, z: 3 } }"),
+        Err(Error::FieldDoesntExist(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> Point { Point { x: 1, y: 'c' } }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f(p: u8) -> Point { Point { x: 1, ..p } }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f() { Line { x: 1 }; }"),
+        Err(Error::StructDoesntExist(_))
+    ));
+    assert!(matches!(
+        check("fn f(p: Point) -> u8 { p.z }"),
+        Err(Error::FieldDoesntExist(_))
+    ));
+    assert!(matches!(
+        check("fn f(p: Point) -> String { p.x }"),
+        Err(Error::TypeMistach(_))
+    ));
+}
+
+#[test]
+fn test_enums() {
+    let check = |code: &str| {
+        check(&format!(
+            "enum E {{ Eof, Failed(String, u32), Moved {{ x: u8, y: u8 }} }} {}",
+            code
+        ))
+    };
+
+    assert!(check("fn f(s: String) -> E { if true { E::Failed(s, 1) } else { E::Eof } }").is_ok());
+    assert!(check("fn f() -> E { E::Moved { x: 1, y: 2 } }").is_ok());
+    assert!(check(
+        "fn f(e: E) -> u32 { match e { E::Failed(_, line) => line, E::Moved { x, .. } => x as u32, E::Eof => 0 } }"
+    )
+    .is_ok());
+
+    assert!(matches!(
+        check("fn f() -> E { E::Missing }"),
+        Err(Error::VariantDoesntExist(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> E { E::Failed(\"s\") }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> E { E::Failed(1, 1) }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> E { E::Eof(1) }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> E { E::Moved { x: 1 } }"),
+        Err(Error::MissingField(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> String { E::Eof }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f(e: E) -> String { match e { E::Failed(s, line) => line, _ => \"\" } }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f(e: E) { match e { E::Failed(s) => () } }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f(e: u8) { match e { E::Eof => () } }"),
+        Err(Error::TypeMistach(_))
+    ));
+}
+
+#[test]
+fn test_loops() {
+    assert!(check("fn f(n: u8) -> u8 { let mut i = 0; while i < n { i += 1; } i }").is_ok());
+    assert!(check("fn f(n: u8) -> u8 { let mut t = 0; for i in 0..n { t += i; } t }").is_ok());
+    assert!(check("fn f() -> u8 { loop { if true { break 1; } break 2u8; } }").is_ok());
+    assert!(check("fn f() -> u8 { loop {} }").is_ok());
+    assert!(check("fn f() { 'outer: loop { for x in 0..10 { if x > 5 { break 'outer; } continue 'outer; } } }").is_ok());
+
+    assert!(matches!(
+        check("fn f() { break; }"),
+        Err(Error::BreakOutsideLoop(_))
+    ));
+    assert!(matches!(
+        check("fn f() { continue; }"),
+        Err(Error::BreakOutsideLoop(_))
+    ));
+    assert!(matches!(
+        check("fn f() { loop { break 'a; } }"),
+        Err(Error::LabelDoesntExist(_))
+    ));
+    assert!(matches!(
+        check("fn f() { while true { break 1; } }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> u8 { loop { break 'c'; } }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> u8 { loop { break 1; break 'c'; } }"),
+        Err(Error::TypeMistach(span)) if span == Span::new(37, 40)
+    ));
+    assert!(matches!(
+        check("fn f() { while 1 {} }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f() { for i in 0u8..'c' {} }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> String { for i in 0u8..9 { return i; } }"),
+        Err(Error::TypeMistach(_))
+    ));
+}
+
+#[test]
+fn test_arrays() {
+    assert!(check("fn f() { let array: &[u8] = [1, 2, 3, 4]; }").is_ok());
+    assert!(check("fn f() -> [u8; 3] { [1, 2u8, 3] }").is_ok());
+    assert!(check("fn f() -> u8 { let buffer = [0u8; 16]; buffer[15] }").is_ok());
+    assert!(check("fn f(bytes: &[u8], i: usize) -> u8 { bytes[i] + bytes[100] }").is_ok());
+    assert!(check("fn f(bytes: &mut [u8; 4]) -> &[u8] { bytes }").is_ok());
+    assert!(check("fn f(a: [u8; 4]) -> u8 { let mut t = 0; for x in a { t += x; } t }").is_ok());
+    assert!(check("fn f(a: [u8; 4]) { let s: [u8] = a[1..3]; }").is_ok());
+
+    assert!(matches!(
+        check("fn f() { [1, 'c']; }"),
+        Err(Error::TypeMistach(span)) if span == Span::new(13, 16)
+    ));
+    assert!(matches!(
+        check("fn f() -> [u8; 2] { [1, 2, 3] }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> [u8; 2] { [1u16; 2] }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f() -> u8 { let a = [0u8; 4]; a[4] }"),
+        Err(Error::IndexOutOfBounds(span)) if span == Span::new(35, 36)
+    ));
+    assert!(matches!(
+        check("fn f(a: [u8; 4]) -> u8 { a[-1] }"),
+        Err(Error::TypeMistach(span)) if span == Span::new(27, 29)
+    ));
+    assert!(matches!(
+        check("fn f(a: [u8; 4], i: u8) -> u8 { a[i] }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f(a: &[u8]) -> &mut [u8] { a }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn f(a: &[u8]) -> [u8; 4] { a }"),
+        Err(Error::TypeMistach(_))
+    ));
+}
+
+#[test]
+fn test_types() {
+    assert!(check("fn f(a: (u8, & str)) -> (u8,&str) { a }").is_ok());
+    assert!(check("fn f(a: &mut &[u8]) -> &&[u8] { a }").is_ok());
+    assert!(check("fn id<T>(t: T) -> T { t } fn f() -> u8 { id(1u8) + id(2) }").is_ok());
+    assert!(check(
+        "fn first<T>(items: &[T], t: T) -> T { t } fn f(a: [u8; 2]) -> u8 { first(a, 0) }"
+    )
+    .is_ok());
+    assert!(check("fn id<T>(t: T) -> T { t } fn f<U>(u: U) -> U { id(u) }").is_ok());
+
+    assert!(matches!(
+        check("fn f(a: (u8, u8)) -> (u8,) { a }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn id<T>(t: T) -> T { t } fn f() -> u8 { id('c') }"),
+        Err(Error::TypeMistach(_))
+    ));
+    assert!(matches!(
+        check("fn pair<T>(a: T, b: T) {} fn f() { pair(1u8, 'c'); }"),
+        Err(Error::TypeMistach(span)) if span == Span::new(45, 48)
+    ));
+    assert!(matches!(
+        check("fn f<T>(t: T) -> u8 { t }"),
+        Err(Error::TypeMistach(_))
+    ));
 }
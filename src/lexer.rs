@@ -2,15 +2,180 @@
 
 // This is the state machine we'll be using to represent lexemes
 
-use std::{iter::Peekable, path::Iter};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::span::{SourceFile, Span};
+use crate::symbol::Symbol;
+
+// lexemes borrow their text from the source being lexed, nothing is copied
+// unless a string literal has escapes that have to be resolved. Names are
+// interned instead
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lexeme<'src> {
+    pub kind: LexemeKind<'src>,
+    pub span: Span,
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Lexeme {
+pub enum LexemeKind<'src> {
     ByteToken(Token),
     VariableByteToken(VarToken),
-    TokenLiteral(Vec<u8>),
+    // `[A-Za-z_][A-Za-z0-9_]*` that isn't a keyword
+    Identifier(Symbol),
+    Integer(IntLiteral<'src>),
+    Float(FloatLiteral<'src>),
+    // double quoted string with the escapes already resolved, borrowed from
+    // the source when there weren't any
+    Str(Cow<'src, str>),
+    Char(char),
+    // `'outer`, the name of a loop without its quote
+    Label(Symbol),
+    // `///` or `//!` comment, plain comments are dropped like whitespace
+    DocComment(DocComment<'src>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntLiteral<'src> {
+    // digits as written, without the radix prefix but with any `_`
+    pub digits: &'src str,
+    pub radix: u32,
+    // type suffix like the `u8` in `10u8`
+    pub suffix: Option<&'src str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FloatLiteral<'src> {
+    // `1.5e3` style text as written, `_` separators included
+    pub text: &'src str,
+    pub suffix: Option<&'src str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocComment<'src> {
+    // everything after the `///` or `//!` up to the end of the line
+    pub text: &'src str,
+    pub style: DocStyle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocStyle {
+    // `///` documents the item that follows
+    Outer,
+    // `//!` documents the enclosing item, at the top of a file the file
+    Inner,
+}
+
+// source text between lexemes, kept by `Lexeme::lex_lossless`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trivia<'src> {
+    pub kind: TriviaKind,
+    pub text: &'src str,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    // run of spaces, tabs and carriage returns
+    Whitespace,
+    // a single `\n`
+    Newline,
+    // `// ...` up to but not including the newline
+    LineComment,
+    // `/* ... */` including any nested comments
+    BlockComment,
+    // input that isn't a lexeme, reported as an error when it was lexed
+    Skipped,
+}
+
+// a lexeme with the trivia around it. Trailing trivia runs to the end of
+// the lexeme's line, including the newline, everything after that leads
+// the next lexeme
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TriviaLexeme<'src> {
+    pub leading: Vec<Trivia<'src>>,
+    pub lexeme: Lexeme<'src>,
+    // the lexeme exactly as it was written
+    pub text: &'src str,
+    pub trailing: Vec<Trivia<'src>>,
+}
+
+// every byte of a source, writing it out with `Display` gives back the
+// exact text that was lexed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LosslessLexemes<'src> {
+    pub lexemes: Vec<TriviaLexeme<'src>>,
+    // trivia after the last lexeme's line, all of it if there are no lexemes
+    pub trailing: Vec<Trivia<'src>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexError {
+    // `"...` with no closing quote, spans to the end of the input
+    UnterminatedString { span: Span },
+    // `'a` with no closing quote
+    UnterminatedChar { span: Span },
+    // `''` or `'ab'`
+    InvalidChar { span: Span },
+    // `/* ...` with no closing `*/`
+    UnterminatedBlockComment { span: Span },
+    // `\q`, `\x80` or `\u{110000}` in a string or char
+    InvalidEscape { span: Span },
+    // `0x`, `0b102`, `1.5u8`, `10if` or a literal too big for any type
+    MalformedNumber { span: Span },
+    // control characters other than whitespace outside of literals
+    ControlCharacter { byte: u8, span: Span },
+    InvalidUtf8 { span: Span },
+    // a character that can't start any token, `@`, `#` or `$`
+    UnexpectedCharacter { character: char, span: Span },
+    // a `TextEdit` whose span is outside the text it's made to or splits a
+    // character
+    InvalidEdit { span: Span },
+}
+
+impl LexError {
+    pub fn span(&self) -> Span {
+        match self {
+            LexError::UnterminatedString { span }
+            | LexError::UnterminatedChar { span }
+            | LexError::InvalidChar { span }
+            | LexError::UnterminatedBlockComment { span }
+            | LexError::InvalidEscape { span }
+            | LexError::MalformedNumber { span }
+            | LexError::ControlCharacter { span, .. }
+            | LexError::InvalidUtf8 { span }
+            | LexError::UnexpectedCharacter { span, .. }
+            | LexError::InvalidEdit { span } => *span,
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexError::UnterminatedString { .. } => write!(f, "unterminated string literal"),
+            LexError::UnterminatedChar { .. } => write!(f, "unterminated char literal"),
+            LexError::InvalidChar { .. } => {
+                write!(f, "char literals must hold exactly one character")
+            }
+            LexError::UnterminatedBlockComment { .. } => write!(f, "unterminated block comment"),
+            LexError::InvalidEscape { .. } => write!(f, "invalid escape sequence"),
+            LexError::MalformedNumber { .. } => write!(f, "malformed number literal"),
+            LexError::ControlCharacter { byte, .. } => {
+                write!(f, "stray control character {:#04x}", byte)
+            }
+            LexError::InvalidUtf8 { .. } => write!(f, "invalid utf-8"),
+            LexError::UnexpectedCharacter { character, .. } => {
+                write!(f, "unexpected character `{}`", character)
+            }
+            LexError::InvalidEdit { .. } => write!(f, "edit doesn't line up with the text"),
+        }
+    }
 }
 
+impl std::error::Error for LexError {}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Token {
     Eq,
@@ -20,11 +185,46 @@ pub enum Token {
     CloseCurly,
     OpenSquare,
     CloseSquare,
-    Newline,
     Comma,
     Ampersand,
     Semicolon,
     Colon,
+    // `::`
+    DoubleColon,
+    Dot,
+    // `..`
+    DotDot,
+    // `..=`
+    DotDotEq,
+    // `->`
+    Arrow,
+    // `=>`
+    FatArrow,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    PlusEq,
+    MinusEq,
+    StarEq,
+    SlashEq,
+    PercentEq,
+    // `==`
+    EqEq,
+    // `!=`
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    // `&&`
+    AndAnd,
+    // `||`
+    OrOr,
+    Pipe,
+    // `!`
+    Bang,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -41,6 +241,40 @@ pub enum VarToken {
     Struct,
     // enum deceleration
     Enum,
+    // mutable binding or parameter
+    Mut,
+    // match expression
+    Match,
+    // boolean literals
+    True,
+    False,
+    // return from a function
+    Return,
+    // loops
+    While,
+    Loop,
+    For,
+    // `for x in iter`
+    In,
+    Break,
+    Continue,
+    // impl block
+    Impl,
+    // trait deceleration
+    Trait,
+    // visibility
+    Pub,
+    // imports and modules
+    Use,
+    Mod,
+    // constant deceleration
+    Const,
+    // `as` cast
+    As,
+    // `self` value
+    SelfValue,
+    // `Self` type
+    SelfType,
 }
 
 // This function maps a `char`
@@ -59,203 +293,1138 @@ impl Token {
             b',' => Some(Token::Comma),
             b']' => Some(Token::CloseSquare),
             b'[' => Some(Token::OpenSquare),
-            b'\n' => Some(Token::Newline),
             b'&' => Some(Token::Ampersand),
             b':' => Some(Token::Colon),
+            b'.' => Some(Token::Dot),
+            b'+' => Some(Token::Plus),
+            b'-' => Some(Token::Minus),
+            b'*' => Some(Token::Star),
+            b'/' => Some(Token::Slash),
+            b'%' => Some(Token::Percent),
+            b'<' => Some(Token::Lt),
+            b'>' => Some(Token::Gt),
+            b'|' => Some(Token::Pipe),
+            b'!' => Some(Token::Bang),
             _ => None,
         }
     }
+
+    // longest token at the start of `bytes`, so `..=` wins over `..` and `.`
+    fn from_bytes(bytes: &[u8]) -> Option<Token> {
+        let token = match bytes {
+            [b'.', b'.', b'=', ..] => Token::DotDotEq,
+            [b'.', b'.', ..] => Token::DotDot,
+            [b':', b':', ..] => Token::DoubleColon,
+            [b'-', b'>', ..] => Token::Arrow,
+            [b'=', b'>', ..] => Token::FatArrow,
+            [b'=', b'=', ..] => Token::EqEq,
+            [b'!', b'=', ..] => Token::NotEq,
+            [b'<', b'=', ..] => Token::LtEq,
+            [b'>', b'=', ..] => Token::GtEq,
+            [b'+', b'=', ..] => Token::PlusEq,
+            [b'-', b'=', ..] => Token::MinusEq,
+            [b'*', b'=', ..] => Token::StarEq,
+            [b'/', b'=', ..] => Token::SlashEq,
+            [b'%', b'=', ..] => Token::PercentEq,
+            [b'&', b'&', ..] => Token::AndAnd,
+            [b'|', b'|', ..] => Token::OrOr,
+            [first, ..] => return Token::from_char(*first),
+            [] => return None,
+        };
+
+        Some(token)
+    }
+}
+
+impl Token {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Token::Eq => "=",
+            Token::OpenParen => "(",
+            Token::CloseParen => ")",
+            Token::OpenCurly => "{",
+            Token::CloseCurly => "}",
+            Token::OpenSquare => "[",
+            Token::CloseSquare => "]",
+            Token::Comma => ",",
+            Token::Ampersand => "&",
+            Token::Semicolon => ";",
+            Token::Colon => ":",
+            Token::DoubleColon => "::",
+            Token::Dot => ".",
+            Token::DotDot => "..",
+            Token::DotDotEq => "..=",
+            Token::Arrow => "->",
+            Token::FatArrow => "=>",
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Star => "*",
+            Token::Slash => "/",
+            Token::Percent => "%",
+            Token::PlusEq => "+=",
+            Token::MinusEq => "-=",
+            Token::StarEq => "*=",
+            Token::SlashEq => "/=",
+            Token::PercentEq => "%=",
+            Token::EqEq => "==",
+            Token::NotEq => "!=",
+            Token::Lt => "<",
+            Token::LtEq => "<=",
+            Token::Gt => ">",
+            Token::GtEq => ">=",
+            Token::AndAnd => "&&",
+            Token::OrOr => "||",
+            Token::Pipe => "|",
+            Token::Bang => "!",
+        }
+    }
 }
 
-impl Lexeme {
-    // tokens we know are classified as Lexemes::ByteToken
-    // in the sequence, anything we don't know about needs to be
-    // classified as Lexemes::TokenLiteral(Vec<u8>)
-    pub fn from_literal(literal: Vec<u8>) -> Vec<Lexeme> {
-        let mut peekable = literal.iter().peekable();
-        // pre-reserve some space on the heap
-        // so we dont need to grow when pushing in new tokens
-        let mut lexems = Vec::with_capacity(literal.len());
-        let mut current_literal: Vec<u8> = Vec::new();
+impl VarToken {
+    // keyword table, `word` has to be a complete identifier
+    fn from_word(word: &[u8]) -> Option<VarToken> {
+        match word {
+            b"fn" => Some(VarToken::Fn),
+            b"let" => Some(VarToken::Let),
+            b"if" => Some(VarToken::If),
+            b"else" => Some(VarToken::Else),
+            b"struct" => Some(VarToken::Struct),
+            b"enum" => Some(VarToken::Enum),
+            b"mut" => Some(VarToken::Mut),
+            b"match" => Some(VarToken::Match),
+            b"true" => Some(VarToken::True),
+            b"false" => Some(VarToken::False),
+            b"return" => Some(VarToken::Return),
+            b"while" => Some(VarToken::While),
+            b"loop" => Some(VarToken::Loop),
+            b"for" => Some(VarToken::For),
+            b"in" => Some(VarToken::In),
+            b"break" => Some(VarToken::Break),
+            b"continue" => Some(VarToken::Continue),
+            b"impl" => Some(VarToken::Impl),
+            b"trait" => Some(VarToken::Trait),
+            b"pub" => Some(VarToken::Pub),
+            b"use" => Some(VarToken::Use),
+            b"mod" => Some(VarToken::Mod),
+            b"const" => Some(VarToken::Const),
+            b"as" => Some(VarToken::As),
+            b"self" => Some(VarToken::SelfValue),
+            b"Self" => Some(VarToken::SelfType),
+            _ => None,
+        }
+    }
 
-        while let Some(curr_token) = peekable.peek() {
-            let curr_token = **curr_token;
-            // First check if our current token is a keyword or not
-            if let Some(x) = Token::from_char(curr_token) {
-                if current_literal.len() > 0 {
-                    lexems.push(Lexeme::TokenLiteral(current_literal.clone()));
-                    current_literal = Vec::new();
-                }
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VarToken::Fn => "fn",
+            VarToken::Let => "let",
+            VarToken::If => "if",
+            VarToken::Else => "else",
+            VarToken::Struct => "struct",
+            VarToken::Enum => "enum",
+            VarToken::Mut => "mut",
+            VarToken::Match => "match",
+            VarToken::True => "true",
+            VarToken::False => "false",
+            VarToken::Return => "return",
+            VarToken::While => "while",
+            VarToken::Loop => "loop",
+            VarToken::For => "for",
+            VarToken::In => "in",
+            VarToken::Break => "break",
+            VarToken::Continue => "continue",
+            VarToken::Impl => "impl",
+            VarToken::Trait => "trait",
+            VarToken::Pub => "pub",
+            VarToken::Use => "use",
+            VarToken::Mod => "mod",
+            VarToken::Const => "const",
+            VarToken::As => "as",
+            VarToken::SelfValue => "self",
+            VarToken::SelfType => "Self",
+        }
+    }
+}
+
+impl IntLiteral<'_> {
+    // `None` when the digits don't fit or aren't valid for the radix
+    pub fn value(&self) -> Option<u128> {
+        u128::from_str_radix(&without_separators(self.digits), self.radix).ok()
+    }
+}
+
+impl FloatLiteral<'_> {
+    pub fn value(&self) -> Option<f64> {
+        without_separators(self.text).parse().ok()
+    }
+}
+
+impl fmt::Display for IntLiteral<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefix = match self.radix {
+            16 => "0x",
+            8 => "0o",
+            2 => "0b",
+            _ => "",
+        };
+
+        write!(f, "{}{}{}", prefix, self.digits, self.suffix.unwrap_or(""))
+    }
+}
+
+impl fmt::Display for FloatLiteral<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.text, self.suffix.unwrap_or(""))
+    }
+}
 
-                lexems.push(Lexeme::ByteToken(x.clone()));
-                peekable.next();
+impl fmt::Display for LexemeKind<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexemeKind::ByteToken(token) => write!(f, "`{}`", token.as_str()),
+            LexemeKind::VariableByteToken(var_token) => write!(f, "`{}`", var_token.as_str()),
+            LexemeKind::Identifier(name) => write!(f, "`{}`", name),
+            LexemeKind::Integer(integer) => write!(f, "`{}`", integer),
+            LexemeKind::Float(float) => write!(f, "`{}`", float),
+            LexemeKind::Str(string) => write!(f, "`{:?}`", string),
+            LexemeKind::Char(racter) => write!(f, "`{:?}`", racter),
+            LexemeKind::Label(label) => write!(f, "`'{}`", label),
+            LexemeKind::DocComment(_) => write!(f, "doc comment"),
+        }
+    }
+}
+
+impl<'src> Lexeme<'src> {
+    // tokens we know are classified as Lexemes::ByteToken, keywords as
+    // Lexemes::VariableByteToken and names, numbers, strings and chars get
+    // their own kind. Whitespace and comments are dropped, anything we
+    // don't know about is an error
+    pub fn from_literal(literal: &'src [u8]) -> Result<Vec<Lexeme<'src>>, Vec<LexError>> {
+        Lexeme::from_literal_at(literal, 0)
+    }
+
+    // `start_pos` is the global offset of the first byte of `literal`
+    pub fn from_literal_at(
+        literal: &'src [u8],
+        start_pos: usize,
+    ) -> Result<Vec<Lexeme<'src>>, Vec<LexError>> {
+        let (lexems, errors) = Lexeme::lex(literal, start_pos);
+
+        if errors.is_empty() {
+            Ok(lexems)
+        } else {
+            Err(errors)
+        }
+    }
 
-                flush_whitespace(&mut peekable);
-                flush_newline(&mut peekable);
-                continue;
+    // like `from_literal_at` but never gives up, bad input is recorded as an
+    // error and skipped so everything around it is still lexed
+    pub fn lex(literal: &'src [u8], start_pos: usize) -> (Vec<Lexeme<'src>>, Vec<LexError>) {
+        let mut lexems = Vec::new();
+        let mut errors = Vec::new();
+
+        for lexeme in Lexer::from_bytes(literal, start_pos) {
+            match lexeme {
+                Ok(lexeme) => lexems.push(lexeme),
+                Err(error) => errors.push(error),
             }
+        }
 
-            peekable.next();
+        (lexems, errors)
+    }
 
-            match curr_token {
-                // possible function deceleration
-                b'f' => {
-                    if let Some(possible_n) = peekable.peek() {
-                        if **possible_n == b'n' {
-                            flush_curr_literal(&mut lexems, &mut current_literal);
+    // like `lex` but nothing is thrown away, whitespace, plain comments and
+    // input that couldn't be lexed are attached to the lexemes around them
+    // as trivia
+    pub fn lex_lossless(
+        src: &'src str,
+        start_pos: usize,
+    ) -> (LosslessLexemes<'src>, Vec<LexError>) {
+        let mut cursor = Cursor {
+            bytes: src.as_bytes(),
+            pos: 0,
+            start_pos,
+            errors: VecDeque::new(),
+        };
+        let mut lossless = LosslessLexemes::default();
+        let mut leading = Vec::new();
 
-                            lexems.push(Lexeme::VariableByteToken(VarToken::Fn));
-                            peekable.next();
+        while cursor.peek().is_some() {
+            let start = cursor.pos;
+            let kind = match cursor.trivia() {
+                Some(kind) => kind,
+                None => match cursor.lexeme() {
+                    Some(lexeme) => {
+                        let mut lexeme = TriviaLexeme {
+                            leading: std::mem::take(&mut leading),
+                            text: &src[start..cursor.pos],
+                            lexeme,
+                            trailing: Vec::new(),
+                        };
 
-                            flush_whitespace(&mut peekable);
-                        } else {
-                            current_literal.push(curr_token);
-                        }
-                    }
-                }
-                // possible let variable deceleration
-                b'l' => {
-                    let mut cloned = peekable.clone();
-                    let e = cloned.next();
-                    let t = cloned.next();
-
-                    match (e, t) {
-                        (Some(b'e'), Some(b't')) => {
-                            flush_curr_literal(&mut lexems, &mut current_literal);
-
-                            lexems.push(Lexeme::VariableByteToken(VarToken::Let));
-                            peekable.next();
-                            peekable.next();
-
-                            flush_whitespace(&mut peekable);
-                        }
-                        _ => {
-                            current_literal.push(curr_token);
-                        }
-                    }
-                }
-                // possible if variable deceleration
-                b'i' => {
-                    if let Some(b'f') = peekable.peek() {
-                        flush_curr_literal(&mut lexems, &mut current_literal);
-
-                        lexems.push(Lexeme::VariableByteToken(VarToken::If));
-                        peekable.next();
-                    } else {
-                        current_literal.push(curr_token);
-                    }
-                }
-                // possible else variable deceleration or enum
-                b'e' => {
-                    let mut cloned = peekable.clone();
-                    let l = cloned.next();
-                    let s = cloned.next();
-                    let e = cloned.next();
-
-                    match (l, s, e) {
-                        (Some(b'l'), Some(b's'), Some(b'e')) => {
-                            flush_curr_literal(&mut lexems, &mut current_literal);
-
-                            lexems.push(Lexeme::VariableByteToken(VarToken::Else));
-                            peekable.next();
-                            peekable.next();
-                            peekable.next();
-                        }
-                        (Some(b'n'), Some(b'u'), Some(b'm')) => {
-                            flush_curr_literal(&mut lexems, &mut current_literal);
-
-                            lexems.push(Lexeme::VariableByteToken(VarToken::Enum));
-                            peekable.next();
-                            peekable.next();
-                            peekable.next();
-                        }
-
-                        _ => {
-                            current_literal.push(curr_token);
-                        }
-                    }
-                }
-                b's' => {
-                    let mut cloned = peekable.clone();
-                    let t = cloned.next();
-                    let r = cloned.next();
-                    let u = cloned.next();
-                    let c = cloned.next();
-                    let t_nd = cloned.next();
-
-                    match (t, r, u, c, t_nd) {
-                        (Some(b't'), Some(b'r'), Some(b'u'), Some(b'c'), Some(b't')) => {
-                            flush_curr_literal(&mut lexems, &mut current_literal);
-
-                            lexems.push(Lexeme::VariableByteToken(VarToken::Struct));
-                            peekable.next();
-                            peekable.next();
-                            peekable.next();
-                            peekable.next();
-                            peekable.next();
-
-                            flush_whitespace(&mut peekable);
-                        }
-                        _ => {
-                            current_literal.push(curr_token);
-                        }
+                        cursor.trailing_trivia(src, &mut lexeme.trailing);
+                        lossless.lexemes.push(lexeme);
+                        continue;
                     }
+                    // the error has been reported, keep the bad input
+                    None => TriviaKind::Skipped,
+                },
+            };
+
+            leading.push(Trivia {
+                kind,
+                text: &src[start..cursor.pos],
+                span: cursor.span(start),
+            });
+        }
+        lossless.trailing = leading;
+
+        (lossless, cursor.errors.into())
+    }
+
+    // Lexes `src` again after `edit` changed it, `old` being the lexemes of
+    // the text before the edit. Lexing restarts at the line the edit is on
+    // and stops as soon as a lexeme lines up with one in `old` past the
+    // edit, from there on the text and so the lexemes are the same as
+    // before. Only errors in the relexed region are returned, an edit whose
+    // text isn't in `src` where its span starts is an error.
+    //
+    // # Panics
+    //
+    // `old` has to be the lexemes of `src` as it was before the edit, lexed
+    // at the same `start_pos`
+    pub fn relex(
+        old: &[Lexeme<'_>],
+        edit: &TextEdit,
+        src: &'src str,
+        start_pos: usize,
+    ) -> Result<(Vec<Lexeme<'src>>, Vec<LexError>), LexError> {
+        let invalid = LexError::InvalidEdit { span: edit.span };
+        let edit_start = match edit.span.start.checked_sub(start_pos) {
+            Some(edit_start) if edit.span.start <= edit.span.end => edit_start,
+            _ => return Err(invalid),
+        };
+        let edit_end = edit_start + edit.text.len();
+        if src.get(edit_start..edit_end) != Some(edit.text.as_str()) {
+            return Err(invalid);
+        }
+
+        // lookahead never goes past the end of a line, so lexemes that ended
+        // before the line with the edit on it can't have changed
+        let line_start = src[..edit_start]
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1);
+        let kept = old.partition_point(|lexeme| lexeme.span.end - start_pos <= line_start);
+        let restart = kept
+            .checked_sub(1)
+            .map_or(0, |last| old[last].span.end - start_pos);
+
+        let mut lexems: Vec<Lexeme<'src>> = old[..kept]
+            .iter()
+            .map(|lexeme| lexeme.rebase(src, start_pos, 0))
+            .collect();
+        let mut errors = Vec::new();
+
+        // old lexemes after the edit, the first one lexing reaches is where
+        // the two streams are back in sync
+        let shift = |lexeme: &Lexeme<'_>| lexeme.span.start - edit.span.len() + edit.text.len();
+        let mut after = old[kept..]
+            .iter()
+            .enumerate()
+            .filter(|(_, lexeme)| lexeme.span.start >= edit.span.end)
+            .peekable();
+
+        for lexeme in Lexer::from_bytes(&src.as_bytes()[restart..], start_pos + restart) {
+            let lexeme = match lexeme {
+                Ok(lexeme) => lexeme,
+                Err(error) => {
+                    errors.push(error);
+                    continue;
                 }
-                _ => {
-                    current_literal.push(curr_token);
+            };
+
+            if lexeme.span.start - start_pos >= edit_end {
+                while after
+                    .next_if(|(_, old)| shift(old) < lexeme.span.start)
+                    .is_some()
+                {}
+
+                if let Some(&(index, _)) = after
+                    .peek()
+                    .filter(|(_, old)| shift(old) == lexeme.span.start)
+                {
+                    let offset = edit.text.len() as isize - edit.span.len() as isize;
+                    lexems.extend(
+                        old[kept + index..]
+                            .iter()
+                            .map(|lexeme| lexeme.rebase(src, start_pos, offset)),
+                    );
+                    break;
                 }
             }
+
+            lexems.push(lexeme);
         }
 
-        lexems
+        Ok((lexems, errors))
+    }
+
+    // the same lexeme moved `offset` bytes, borrowing from `src` instead
+    fn rebase<'new>(&self, src: &'new str, start_pos: usize, offset: isize) -> Lexeme<'new> {
+        let span = Span::new(
+            self.span.start.wrapping_add_signed(offset),
+            self.span.end.wrapping_add_signed(offset),
+        );
+        let text = &src[span.start - start_pos..span.end - start_pos];
+
+        let kind = match &self.kind {
+            LexemeKind::ByteToken(token) => LexemeKind::ByteToken(token.clone()),
+            LexemeKind::VariableByteToken(keyword) => {
+                LexemeKind::VariableByteToken(keyword.clone())
+            }
+            LexemeKind::Identifier(name) => LexemeKind::Identifier(*name),
+            LexemeKind::Integer(integer) => {
+                let prefix = if integer.radix == 10 { 0 } else { 2 };
+
+                LexemeKind::Integer(IntLiteral {
+                    digits: &text[prefix..prefix + integer.digits.len()],
+                    radix: integer.radix,
+                    suffix: integer
+                        .suffix
+                        .map(|suffix| &text[text.len() - suffix.len()..]),
+                })
+            }
+            LexemeKind::Float(float) => LexemeKind::Float(FloatLiteral {
+                text: &text[..float.text.len()],
+                suffix: float
+                    .suffix
+                    .map(|suffix| &text[text.len() - suffix.len()..]),
+            }),
+            LexemeKind::Str(Cow::Borrowed(string)) => {
+                LexemeKind::Str(Cow::Borrowed(&text[1..1 + string.len()]))
+            }
+            LexemeKind::Str(Cow::Owned(string)) => LexemeKind::Str(Cow::Owned(string.clone())),
+            LexemeKind::Char(racter) => LexemeKind::Char(*racter),
+            LexemeKind::Label(label) => LexemeKind::Label(*label),
+            LexemeKind::DocComment(doc) => LexemeKind::DocComment(DocComment {
+                text: &text[3..],
+                style: doc.style,
+            }),
+        };
+
+        Lexeme { kind, span }
     }
 }
 
-fn flush_curr_literal(lexems: &mut Vec<Lexeme>, current_literal: &mut Vec<u8>) {
-    if current_literal.len() > 0 {
-        lexems.push(Lexeme::TokenLiteral(current_literal.clone()));
+// replaces the text covered by `span` with `text`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub span: Span,
+    pub text: String,
+}
+
+impl TextEdit {
+    // `src` with the edit made, `start_pos` is the global offset of `src`.
+    // Editors send whatever they like, a span outside of `src` or splitting
+    // a character is an error
+    pub fn apply(&self, src: &str, start_pos: usize) -> Result<String, LexError> {
+        let start = self.span.start.checked_sub(start_pos);
+        let end = self.span.end.checked_sub(start_pos);
+
+        let (Some(start), Some(end)) = (start, end) else {
+            return Err(LexError::InvalidEdit { span: self.span });
+        };
+        if src.get(start..end).is_none() {
+            return Err(LexError::InvalidEdit { span: self.span });
+        }
 
-        current_literal.clear();
+        Ok([&src[..start], self.text.as_str(), &src[end..]].concat())
     }
 }
 
-fn flush_whitespace<'a, I: Iterator<Item = &'a u8>>(peekable: &mut Peekable<I>) {
-    while Some(&&b' ') == peekable.peek() {
-        peekable.next();
+impl fmt::Display for TriviaLexeme<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for trivia in &self.leading {
+            f.write_str(trivia.text)?;
+        }
+        f.write_str(self.text)?;
+        for trivia in &self.trailing {
+            f.write_str(trivia.text)?;
+        }
+
+        Ok(())
     }
 }
 
-fn flush_newline<'a, I: Iterator<Item = &'a u8>>(peekable: &mut Peekable<I>) {
-    while Some(&&b'\n') == peekable.peek() {
-        peekable.next();
+impl fmt::Display for LosslessLexemes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for lexeme in &self.lexemes {
+            write!(f, "{}", lexeme)?;
+        }
+        for trivia in &self.trailing {
+            f.write_str(trivia.text)?;
+        }
+
+        Ok(())
     }
 }
 
-fn main() {
-    let code = "fn main() {
-        let array = [0, 1, 2];
+// Pulls lexemes out of the source one at a time, only scanning as far as
+// the next lexeme. Errors come out of the iterator in source order, bad
+// input is skipped so lexing can carry on after an `Err`
+pub struct Lexer<'src> {
+    cursor: Cursor<'src>,
+    // lexeme found while scanning, handed out once the errors reported
+    // before it have been
+    pending: Option<Lexeme<'src>>,
+}
 
-        if true { test } else {};
-    }";
+impl<'src> Lexer<'src> {
+    pub fn new(src: &'src str) -> Lexer<'src> {
+        Lexer::from_bytes(src.as_bytes(), 0)
+    }
+
+    // lex a file registered in a `SourceMap`, spans are offset by the
+    // file's start position so they can be looked up in the map
+    pub fn from_file(file: &'src SourceFile) -> Lexer<'src> {
+        Lexer::from_bytes(file.src.as_bytes(), file.start_pos)
+    }
+
+    // `start_pos` is the global offset of `bytes[0]`, bytes that aren't
+    // utf-8 are reported as errors
+    pub fn from_bytes(bytes: &'src [u8], start_pos: usize) -> Lexer<'src> {
+        Lexer {
+            cursor: Cursor {
+                bytes,
+                pos: 0,
+                start_pos,
+                errors: VecDeque::new(),
+            },
+            pending: None,
+        }
+    }
+}
+
+impl<'src> Iterator for Lexer<'src> {
+    type Item = Result<Lexeme<'src>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(error) = self.cursor.errors.pop_front() {
+                return Some(Err(error));
+            }
+
+            if let Some(lexeme) = self.pending.take() {
+                return Some(Ok(lexeme));
+            }
+
+            self.cursor.peek()?;
+            self.pending = self.cursor.lexeme();
+        }
+    }
+}
+
+fn is_word_start(byte: u8) -> bool {
+    byte.is_ascii_alphabetic() || byte == b'_'
+}
+
+fn is_word_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
 
-    let bytes = code.as_bytes().to_vec();
+// number suffixes we know the type of
+fn is_int_suffix(suffix: &str) -> bool {
+    matches!(
+        suffix,
+        "i8" | "i16"
+            | "i32"
+            | "i64"
+            | "i128"
+            | "isize"
+            | "u8"
+            | "u16"
+            | "u32"
+            | "u64"
+            | "u128"
+            | "usize"
+    )
+}
 
-    println!("code: {}", code);
+fn is_float_suffix(suffix: &str) -> bool {
+    matches!(suffix, "f32" | "f64")
+}
 
-    println!("Lexed: {:?}", Lexeme::from_literal(bytes));
+// position in the bytes being lexed
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    // global offset of `bytes[0]`
+    start_pos: usize,
+    // handed out from the front by `Lexer` as they're found
+    errors: VecDeque<LexError>,
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.peek_nth(0)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<u8> {
+        self.bytes.get(self.pos + n).copied()
+    }
+
+    fn eat_while(&mut self, predicate: impl Fn(u8) -> bool) -> &'a [u8] {
+        let start = self.pos;
+        while self.peek().is_some_and(&predicate) {
+            self.pos += 1;
+        }
+
+        &self.bytes[start..self.pos]
+    }
+
+    // span from `start` (relative to `bytes`) up to the cursor
+    fn span(&self, start: usize) -> Span {
+        Span::new(self.start_pos + start, self.start_pos + self.pos)
+    }
+
+    // `bytes[start..self.pos]` as text, callers only ask for runs they know
+    // are ascii or have already checked
+    fn text(&self, start: usize) -> &'a str {
+        std::str::from_utf8(&self.bytes[start..self.pos]).unwrap_or("")
+    }
+
+    // scans past whitespace to the next lexeme, `None` when what was found
+    // is a plain comment or bad input, which is reported
+    fn lexeme(&mut self) -> Option<Lexeme<'a>> {
+        while self.trivia().is_some() {}
+
+        let curr_token = self.peek()?;
+        let start = self.pos;
+
+        let kind = if let Some(style) = self.doc_style() {
+            LexemeKind::DocComment(self.doc_comment(style))
+        } else if let Some(token) = Token::from_bytes(&self.bytes[self.pos..]) {
+            self.pos += token.as_str().len();
+            LexemeKind::ByteToken(token)
+        } else if is_word_start(curr_token) {
+            // identifiers and keywords are maximal runs of word bytes, a
+            // keyword is only recognised when the whole run matches, so
+            // `often` or `gift` stay identifiers
+            let word = self.eat_while(is_word_byte);
+
+            match VarToken::from_word(word) {
+                Some(keyword) => LexemeKind::VariableByteToken(keyword),
+                None => LexemeKind::Identifier(Symbol::intern(self.text(start))),
+            }
+        } else if curr_token.is_ascii_digit() {
+            self.number()?
+        } else if curr_token == b'"' {
+            self.string()
+        } else if let Some(label) = self.label() {
+            LexemeKind::Label(label)
+        } else if curr_token == b'\'' {
+            LexemeKind::Char(self.char_literal()?)
+        } else {
+            self.unexpected();
+            return None;
+        };
+
+        Some(Lexeme {
+            kind,
+            span: self.span(start),
+        })
+    }
+
+    // trivia after a lexeme up to and including the end of its line
+    fn trailing_trivia(&mut self, src: &'a str, trailing: &mut Vec<Trivia<'a>>) {
+        let mut start = self.pos;
+
+        while let Some(kind) = self.trivia() {
+            trailing.push(Trivia {
+                kind,
+                text: &src[start..self.pos],
+                span: self.span(start),
+            });
+
+            if kind == TriviaKind::Newline {
+                break;
+            }
+            start = self.pos;
+        }
+    }
+
+    // the utf-8 char starting at the cursor, invalid utf-8 is reported and
+    // becomes a replacement char one byte long
+    fn bump_char(&mut self) -> Option<char> {
+        let first = self.peek()?;
+        let len = match first {
+            0xf0..=0xff => 4,
+            0xe0..=0xef => 3,
+            0xc0..=0xdf => 2,
+            _ => 1,
+        };
+
+        match self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+        {
+            Some(racter) => {
+                self.pos += len;
+                racter.chars().next()
+            }
+            None => {
+                let start = self.pos;
+                self.pos += 1;
+                self.errors.push_back(LexError::InvalidUtf8 {
+                    span: self.span(start),
+                });
+
+                Some(char::REPLACEMENT_CHARACTER)
+            }
+        }
+    }
+
+    // a byte that can't start a token
+    fn unexpected(&mut self) {
+        let start = self.pos;
+        let byte = self.bytes[start];
+
+        if byte.is_ascii_control() {
+            self.pos += 1;
+            self.errors.push_back(LexError::ControlCharacter {
+                byte,
+                span: self.span(start),
+            });
+            return;
+        }
+
+        let errors = self.errors.len();
+        if let Some(character) = self.bump_char() {
+            // invalid utf-8 was already reported by `bump_char`
+            if self.errors.len() == errors {
+                self.errors.push_back(LexError::UnexpectedCharacter {
+                    character,
+                    span: self.span(start),
+                });
+            }
+        }
+    }
+
+    // whitespace, a newline or a plain comment at the cursor, doc comments
+    // are lexemes so they aren't trivia
+    fn trivia(&mut self) -> Option<TriviaKind> {
+        let kind = match (self.peek()?, self.peek_nth(1)) {
+            (b'\n', _) => {
+                self.pos += 1;
+                TriviaKind::Newline
+            }
+            (byte, _) if byte.is_ascii_whitespace() => {
+                self.eat_while(|byte| byte.is_ascii_whitespace() && byte != b'\n');
+                TriviaKind::Whitespace
+            }
+            _ if self.doc_style().is_some() => return None,
+            (b'/', Some(b'/')) => {
+                self.eat_while(|byte| byte != b'\n');
+                TriviaKind::LineComment
+            }
+            (b'/', Some(b'*')) => {
+                self.block_comment();
+                TriviaKind::BlockComment
+            }
+            _ => return None,
+        };
+
+        Some(kind)
+    }
+
+    // style of the `///` or `//!` comment at the cursor
+    fn doc_style(&self) -> Option<DocStyle> {
+        if self.peek() != Some(b'/') || self.peek_nth(1) != Some(b'/') {
+            return None;
+        }
+
+        match (self.peek_nth(2), self.peek_nth(3)) {
+            // `////` is a plain comment
+            (Some(b'/'), Some(b'/')) => None,
+            (Some(b'/'), _) => Some(DocStyle::Outer),
+            (Some(b'!'), _) => Some(DocStyle::Inner),
+            _ => None,
+        }
+    }
+
+    fn doc_comment(&mut self, style: DocStyle) -> DocComment<'a> {
+        let start = self.pos;
+        let line = self.eat_while(|byte| byte != b'\n');
+
+        let text = match std::str::from_utf8(&line[3..]) {
+            Ok(text) => text,
+            Err(_) => {
+                self.errors.push_back(LexError::InvalidUtf8 {
+                    span: self.span(start),
+                });
+                ""
+            }
+        };
+
+        DocComment { text, style }
+    }
+
+    // block comments nest, `/* /* */ */` is a single comment
+    fn block_comment(&mut self) {
+        let start = self.pos;
+        self.pos += 2;
+        let mut depth = 1;
+
+        while depth > 0 {
+            match (self.peek(), self.peek_nth(1)) {
+                (None, _) => {
+                    self.errors.push_back(LexError::UnterminatedBlockComment {
+                        span: self.span(start),
+                    });
+                    break;
+                }
+                (Some(b'/'), Some(b'*')) => {
+                    depth += 1;
+                    self.pos += 2;
+                }
+                (Some(b'*'), Some(b'/')) => {
+                    depth -= 1;
+                    self.pos += 2;
+                }
+                _ => self.pos += 1,
+            }
+        }
+    }
+
+    // `10`, `1_000u32`, `0xff`, `0o17`, `0b1010`, `1.5`, `2e10`, `1f32`
+    fn number(&mut self) -> Option<LexemeKind<'a>> {
+        let start = self.pos;
+        let radix = match (self.peek(), self.peek_nth(1)) {
+            (Some(b'0'), Some(b'x')) => 16,
+            (Some(b'0'), Some(b'o')) => 8,
+            (Some(b'0'), Some(b'b')) => 2,
+            _ => 10,
+        };
+
+        let kind = if radix != 10 {
+            self.pos += 2;
+            let digits = match radix {
+                16 => self.eat_while(|byte| byte.is_ascii_hexdigit() || byte == b'_'),
+                // anything that looks like a digit so `0b102` is one bad literal
+                _ => self.eat_while(|byte| byte.is_ascii_digit() || byte == b'_'),
+            };
+            // only ascii digits and `_` were eaten
+            let digits = std::str::from_utf8(digits).unwrap_or("");
+            let suffix = self.suffix();
+
+            let valid = digits.bytes().any(|digit| digit != b'_')
+                && digits
+                    .chars()
+                    .all(|digit| digit == '_' || digit.is_digit(radix))
+                && suffix.is_none_or(is_int_suffix);
+
+            valid.then_some(LexemeKind::Integer(IntLiteral {
+                digits,
+                radix,
+                suffix,
+            }))
+        } else {
+            let mut is_float = false;
+            self.eat_while(|byte| byte.is_ascii_digit() || byte == b'_');
+
+            // `1.5` is a float but `0..n` and `1.max(2)` aren't
+            if self.peek() == Some(b'.')
+                && self.peek_nth(1).is_some_and(|byte| byte.is_ascii_digit())
+            {
+                is_float = true;
+                self.pos += 1;
+                self.eat_while(|byte| byte.is_ascii_digit() || byte == b'_');
+            }
+
+            if let Some(b'e' | b'E') = self.peek() {
+                let digit_at = match self.peek_nth(1) {
+                    Some(b'+' | b'-') => 2,
+                    _ => 1,
+                };
+
+                if self
+                    .peek_nth(digit_at)
+                    .is_some_and(|byte| byte.is_ascii_digit())
+                {
+                    is_float = true;
+                    self.pos += digit_at;
+                    self.eat_while(|byte| byte.is_ascii_digit() || byte == b'_');
+                }
+            }
+
+            let text = self.text(start);
+            let suffix = self.suffix();
+
+            match suffix {
+                Some(float) if is_float_suffix(float) => {
+                    Some(LexemeKind::Float(FloatLiteral { text, suffix }))
+                }
+                None if is_float => Some(LexemeKind::Float(FloatLiteral { text, suffix })),
+                Some(int) if !is_float && is_int_suffix(int) => {
+                    Some(LexemeKind::Integer(IntLiteral {
+                        digits: text,
+                        radix,
+                        suffix,
+                    }))
+                }
+                None => Some(LexemeKind::Integer(IntLiteral {
+                    digits: text,
+                    radix,
+                    suffix,
+                })),
+                _ => None,
+            }
+        };
+
+        // the literal has to fit in the widest integer type
+        let kind = kind.filter(|kind| match kind {
+            LexemeKind::Integer(integer) => integer.value().is_some(),
+            _ => true,
+        });
+
+        if kind.is_none() {
+            self.errors.push_back(LexError::MalformedNumber {
+                span: self.span(start),
+            });
+        }
+
+        kind
+    }
+
+    fn suffix(&mut self) -> Option<&'a str> {
+        if !self.peek().is_some_and(is_word_start) {
+            return None;
+        }
+
+        let start = self.pos;
+        self.eat_while(is_word_byte);
+
+        Some(self.text(start))
+    }
+
+    // `"..."`, strings can span lines
+    fn string(&mut self) -> LexemeKind<'a> {
+        let start = self.pos;
+        self.pos += 1;
+        // the contents are borrowed until an escape or invalid utf-8 means
+        // they have to be rewritten
+        let mut owned: Option<String> = None;
+        let mut end;
+
+        loop {
+            end = self.pos;
+
+            match self.peek() {
+                None => {
+                    self.errors.push_back(LexError::UnterminatedString {
+                        span: self.span(start),
+                    });
+                    break;
+                }
+                Some(b'"') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b'\\') => {
+                    let string = owned.get_or_insert_with(|| self.text(start + 1).to_string());
+                    string.extend(self.escape());
+                }
+                Some(_) => {
+                    let errors = self.errors.len();
+                    let racter = self.bump_char();
+
+                    if self.errors.len() != errors && owned.is_none() {
+                        owned = Some(
+                            std::str::from_utf8(&self.bytes[start + 1..end])
+                                .unwrap_or("")
+                                .to_string(),
+                        );
+                    }
+
+                    if let Some(string) = &mut owned {
+                        string.extend(racter);
+                    }
+                }
+            }
+        }
+
+        match owned {
+            Some(string) => LexemeKind::Str(Cow::Owned(string)),
+            None => LexemeKind::Str(Cow::Borrowed(
+                std::str::from_utf8(&self.bytes[start + 1..end]).unwrap_or(""),
+            )),
+        }
+    }
+
+    // `'name` not closed by another `'`, `'a'` is left as a char
+    fn label(&mut self) -> Option<Symbol> {
+        if self.peek() != Some(b'\'')
+            || !self
+                .bytes
+                .get(self.pos + 1)
+                .copied()
+                .is_some_and(is_word_start)
+        {
+            return None;
+        }
+
+        let end = self.bytes[self.pos + 1..]
+            .iter()
+            .position(|byte| !is_word_byte(*byte))
+            .map_or(self.bytes.len(), |len| self.pos + 1 + len);
+        if self.bytes.get(end) == Some(&b'\'') {
+            return None;
+        }
+
+        let start = self.pos + 1;
+        self.pos = end;
+
+        Some(Symbol::intern(self.text(start)))
+    }
+
+    // `'a'` or `'\n'`
+    fn char_literal(&mut self) -> Option<char> {
+        let start = self.pos;
+        self.pos += 1;
+
+        let racter = match self.peek() {
+            None | Some(b'\n') => None,
+            Some(b'\'') => {
+                self.pos += 1;
+                self.errors.push_back(LexError::InvalidChar {
+                    span: self.span(start),
+                });
+                return None;
+            }
+            // an invalid escape is already reported
+            Some(b'\\') => Some(self.escape().unwrap_or(char::REPLACEMENT_CHARACTER)),
+            Some(_) => self.bump_char(),
+        };
+
+        if self.peek() == Some(b'\'') {
+            self.pos += 1;
+            return racter;
+        }
+
+        // `'ab'` is a char with too much in it, `'a` is missing its quote
+        let rest = self.bytes[self.pos..]
+            .iter()
+            .take_while(|byte| **byte != b'\n')
+            .position(|byte| *byte == b'\'');
+
+        match rest {
+            Some(rest) if racter.is_some() && rest < 8 => {
+                self.pos += rest + 1;
+                self.errors.push_back(LexError::InvalidChar {
+                    span: self.span(start),
+                });
+            }
+            _ => self.errors.push_back(LexError::UnterminatedChar {
+                span: self.span(start),
+            }),
+        }
+
+        None
+    }
+
+    // escape sequence starting at a `\`, invalid escapes are reported
+    fn escape(&mut self) -> Option<char> {
+        let start = self.pos;
+        self.pos += 1;
+
+        let escaped = match self.peek() {
+            Some(b'n') => Some('\n'),
+            Some(b't') => Some('\t'),
+            Some(b'r') => Some('\r'),
+            Some(b'0') => Some('\0'),
+            Some(b'\\') => Some('\\'),
+            Some(b'"') => Some('"'),
+            Some(b'\'') => Some('\''),
+            Some(b'x') => self
+                .bytes
+                .get(self.pos + 1..self.pos + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .filter(|byte| byte.is_ascii())
+                .map(|byte| {
+                    self.pos += 2;
+                    byte as char
+                }),
+            Some(b'u') if self.peek_nth(1) == Some(b'{') => {
+                let digits = self.bytes[self.pos + 2..]
+                    .iter()
+                    .take_while(|byte| byte.is_ascii_hexdigit())
+                    .count();
+                let close = self.pos + 2 + digits;
+
+                if self.bytes.get(close) == Some(&b'}') {
+                    let hex = std::str::from_utf8(&self.bytes[self.pos + 2..close]).unwrap_or("");
+                    let racter = u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+
+                    if racter.is_some() {
+                        self.pos = close;
+                    }
+
+                    racter
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        match escaped {
+            Some(racter) => {
+                self.pos += 1;
+                Some(racter)
+            }
+            None => {
+                // skip the backslash and the char after it
+                self.bump_char();
+                self.errors.push_back(LexError::InvalidEscape {
+                    span: self.span(start),
+                });
+                None
+            }
+        }
+    }
+}
+
+fn without_separators(digits: &str) -> String {
+    digits.chars().filter(|digit| *digit != '_').collect()
+}
+
+// the kinds of the lexemes in `code`, which has to lex without errors
+#[cfg(test)]
+fn lex(code: &str) -> Vec<LexemeKind<'_>> {
+    Lexeme::from_literal(code.as_bytes())
+        .unwrap()
+        .into_iter()
+        .map(|lexeme| lexeme.kind)
+        .collect()
 }
 
 #[test]
 fn test_with_all_keywords() {
-    use Lexeme::*;
+    use LexemeKind::*;
     use Token::*;
     use VarToken::*;
 
-    let code = "fn(){let[,];ifconditionelse&}";
+    let code = "fn(){let[,];if condition else&}";
 
-    let bytes = code.as_bytes().to_vec();
+    let bytes = code.as_bytes();
 
-    let lexed = Lexeme::from_literal(bytes);
+    let lexed: Vec<LexemeKind> = Lexeme::from_literal(bytes)
+        .unwrap()
+        .into_iter()
+        .map(|lexeme| lexeme.kind)
+        .collect();
 
     assert_eq!(
         vec![
@@ -269,7 +1438,7 @@ fn test_with_all_keywords() {
             ByteToken(CloseSquare),
             ByteToken(Semicolon),
             VariableByteToken(If),
-            TokenLiteral([99, 111, 110, 100, 116, 111, 110].to_vec()),
+            Identifier(Symbol::intern("condition")),
             VariableByteToken(Else),
             ByteToken(Ampersand),
             ByteToken(CloseCurly)
@@ -280,7 +1449,7 @@ fn test_with_all_keywords() {
 
 #[test]
 fn test_normal_code() {
-    use Lexeme::*;
+    use LexemeKind::*;
     use Token::*;
     use VarToken::*;
 
@@ -290,51 +1459,603 @@ fn test_normal_code() {
         if true { test } else {};
     }";
 
-    let bytes = code.as_bytes().to_vec();
+    let bytes = code.as_bytes();
+
+    let lexed: Vec<LexemeKind> = Lexeme::from_literal(bytes)
+        .unwrap()
+        .into_iter()
+        .map(|lexeme| lexeme.kind)
+        .collect();
 
-    let lexed = Lexeme::from_literal(bytes);
+    let int = |digits: &'static str| {
+        Integer(IntLiteral {
+            digits,
+            radix: 10,
+            suffix: None,
+        })
+    };
 
     assert_eq!(
         vec![
-            TokenLiteral([32].to_vec()),
             VariableByteToken(Fn),
-            TokenLiteral([32, 109, 97, 110].to_vec()),
+            Identifier(Symbol::intern("main")),
             ByteToken(OpenParen),
             ByteToken(CloseParen),
-            TokenLiteral([32].to_vec()),
             ByteToken(OpenCurly),
-            ByteToken(Newline),
-            TokenLiteral([32, 32, 32, 32, 32, 32, 32, 32].to_vec()),
             VariableByteToken(Let),
-            TokenLiteral([32, 97, 114, 114, 97, 121, 32].to_vec()),
+            Identifier(Symbol::intern("array")),
             ByteToken(Eq),
-            TokenLiteral([32].to_vec()),
             ByteToken(OpenSquare),
-            TokenLiteral([48].to_vec()),
+            int("0"),
             ByteToken(Comma),
-            TokenLiteral([32, 49].to_vec()),
+            int("1"),
             ByteToken(Comma),
-            TokenLiteral([32, 50].to_vec()),
+            int("2"),
             ByteToken(CloseSquare),
             ByteToken(Semicolon),
-            ByteToken(Newline),
-            ByteToken(Newline),
-            TokenLiteral([32, 32, 32, 32, 32, 32, 32, 32].to_vec()),
             VariableByteToken(If),
-            TokenLiteral([32, 116, 114, 117, 101, 32].to_vec()),
+            VariableByteToken(True),
             ByteToken(OpenCurly),
-            TokenLiteral([32, 116, 101, 115, 116, 32].to_vec()),
+            Identifier(Symbol::intern("test")),
             ByteToken(CloseCurly),
-            TokenLiteral([32].to_vec()),
             VariableByteToken(Else),
-            TokenLiteral([32].to_vec()),
             ByteToken(OpenCurly),
             ByteToken(CloseCurly),
             ByteToken(Semicolon),
-            ByteToken(Newline),
-            TokenLiteral([32, 32, 32, 32].to_vec()),
             ByteToken(CloseCurly)
         ],
         lexed
     );
 }
+
+#[test]
+fn test_keywords_need_word_boundaries() {
+    use LexemeKind::*;
+    use VarToken::*;
+
+    // identifiers containing keywords stay whole
+    for code in [
+        "often",
+        "fnord",
+        "gift",
+        "ifconditionelse",
+        "list",
+        "letter",
+        "define",
+        "elsewhere",
+        "structure",
+        "enumerate",
+        "_fn",
+        "fn2",
+    ] {
+        assert_eq!(lex(code), vec![Identifier(Symbol::intern(code))]);
+    }
+
+    assert_eq!(
+        lex("fn define"),
+        vec![VariableByteToken(Fn), Identifier(Symbol::intern("define"))]
+    );
+    // a word right after a digit is a literal suffix
+    assert_eq!(
+        lex("10u8"),
+        vec![Integer(IntLiteral {
+            digits: "10",
+            radix: 10,
+            suffix: Some("u8"),
+        })]
+    );
+}
+
+#[test]
+fn test_literals() {
+    use LexemeKind::*;
+
+    let int = |digits: &'static str, radix: u32, suffix: Option<&'static str>| {
+        Integer(IntLiteral {
+            digits,
+            radix,
+            suffix,
+        })
+    };
+    let float =
+        |text: &'static str, suffix: Option<&'static str>| Float(FloatLiteral { text, suffix });
+
+    assert_eq!(
+        lex("1_000 10u8 0xff_ff 0o17 0b1010_i8"),
+        vec![
+            int("1_000", 10, None),
+            int("10", 10, Some("u8")),
+            int("ff_ff", 16, None),
+            int("17", 8, None),
+            int("1010_", 2, Some("i8")),
+        ]
+    );
+    let values: Vec<_> = lex("1_000 0xff_ff 0b1010_i8")
+        .iter()
+        .map(|kind| match kind {
+            Integer(integer) => integer.value(),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(values, vec![Some(1000), Some(0xffff), Some(0b1010)]);
+    assert_eq!(
+        lex("1.5 2e10 1.5E-3 1f32 3.0_f64"),
+        vec![
+            float("1.5", None),
+            float("2e10", None),
+            float("1.5E-3", None),
+            float("1", Some("f32")),
+            float("3.0_", Some("f64")),
+        ]
+    );
+    assert_eq!(lex("0xff")[0], int("ff", 16, None));
+    assert_eq!(
+        lex(r#""hello world" "tab\t\"quote\" \u{1F600}" 'a' '\n' '\''"#),
+        vec![
+            Str("hello world".into()),
+            Str("tab\t\"quote\" \u{1F600}".into()),
+            Char('a'),
+            Char('\n'),
+            Char('\''),
+        ]
+    );
+    // `0..n` is a range not a float
+    assert_eq!(
+        lex("0..n"),
+        vec![
+            int("0", 10, None),
+            ByteToken(Token::DotDot),
+            Identifier(Symbol::intern("n")),
+        ]
+    );
+}
+
+#[test]
+fn test_operators() {
+    use LexemeKind::*;
+    use Token::*;
+
+    assert_eq!(
+        lex("+ - * / % == != < <= > >= && || ! += -= *= /= %= = | . .. ..= :: : -> =>")
+            .into_iter()
+            .map(|kind| match kind {
+                ByteToken(token) => token,
+                kind => panic!("expected an operator, found {:?}", kind),
+            })
+            .collect::<Vec<_>>(),
+        vec![
+            Plus,
+            Minus,
+            Star,
+            Slash,
+            Percent,
+            EqEq,
+            NotEq,
+            Lt,
+            LtEq,
+            Gt,
+            GtEq,
+            AndAnd,
+            OrOr,
+            Bang,
+            PlusEq,
+            MinusEq,
+            StarEq,
+            SlashEq,
+            PercentEq,
+            Eq,
+            Pipe,
+            Dot,
+            DotDot,
+            DotDotEq,
+            DoubleColon,
+            Colon,
+            Arrow,
+            FatArrow,
+        ]
+    );
+
+    // longest match even without spaces
+    assert_eq!(
+        lex("T::None"),
+        vec![
+            Identifier(Symbol::intern("T")),
+            ByteToken(DoubleColon),
+            Identifier(Symbol::intern("None")),
+        ]
+    );
+    assert_eq!(
+        lex("x<=-y"),
+        vec![
+            Identifier(Symbol::intern("x")),
+            ByteToken(LtEq),
+            ByteToken(Minus),
+            Identifier(Symbol::intern("y")),
+        ]
+    );
+    assert_eq!(lex("fn f(x: String) -> String")[7], ByteToken(Arrow));
+    assert_eq!(
+        lex("arg.push_str(y += x)")[1..],
+        [
+            ByteToken(Dot),
+            Identifier(Symbol::intern("push_str")),
+            ByteToken(OpenParen),
+            Identifier(Symbol::intern("y")),
+            ByteToken(PlusEq),
+            Identifier(Symbol::intern("x")),
+            ByteToken(CloseParen),
+        ]
+    );
+}
+
+#[test]
+fn test_spans() {
+    let code = "fn main(arg: String) {}";
+
+    let lexed = Lexeme::from_literal(code.as_bytes()).unwrap();
+    let spans: Vec<&str> = lexed
+        .iter()
+        .map(|lexeme| &code[lexeme.span.start..lexeme.span.end])
+        .collect();
+
+    assert_eq!(
+        vec!["fn", "main", "(", "arg", ":", "String", ")", "{", "}"],
+        spans
+    );
+
+    // spans are shifted by the start position of the file
+    let lexed = Lexeme::from_literal_at(code.as_bytes(), 100).unwrap();
+
+    assert_eq!(lexed[1].span, crate::span::Span::new(103, 107));
+}
+
+#[test]
+fn test_comments() {
+    use LexemeKind::*;
+
+    assert_eq!(
+        lex("let x = 5; // Immutable\nx /* a /* nested */ comment */ y //// not a doc"),
+        vec![
+            VariableByteToken(VarToken::Let),
+            Identifier(Symbol::intern("x")),
+            ByteToken(Token::Eq),
+            Integer(IntLiteral {
+                digits: "5",
+                radix: 10,
+                suffix: None,
+            }),
+            ByteToken(Token::Semicolon),
+            Identifier(Symbol::intern("x")),
+            Identifier(Symbol::intern("y")),
+        ]
+    );
+    assert_eq!(
+        lex("//! the file\n/// the function\nfn"),
+        vec![
+            DocComment(crate::lexer::DocComment {
+                text: " the file",
+                style: DocStyle::Inner,
+            }),
+            DocComment(crate::lexer::DocComment {
+                text: " the function",
+                style: DocStyle::Outer,
+            }),
+            VariableByteToken(VarToken::Fn),
+        ]
+    );
+}
+
+#[test]
+fn test_errors() {
+    use LexError::*;
+
+    let errors = |code: &[u8]| Lexeme::from_literal(code).unwrap_err();
+
+    assert_eq!(
+        errors(b"\"abc"),
+        vec![UnterminatedString {
+            span: Span::new(0, 4)
+        }]
+    );
+    // `'a` on its own is a label
+    assert_eq!(
+        errors(b"'1"),
+        vec![UnterminatedChar {
+            span: Span::new(0, 2)
+        }]
+    );
+    assert_eq!(
+        errors(b"'' 'ab'"),
+        vec![
+            InvalidChar {
+                span: Span::new(0, 2)
+            },
+            InvalidChar {
+                span: Span::new(3, 7)
+            }
+        ]
+    );
+    // an unterminated block comment swallows the rest of the input
+    assert_eq!(
+        errors(b"x /* /* */"),
+        vec![UnterminatedBlockComment {
+            span: Span::new(2, 10)
+        }]
+    );
+    assert_eq!(
+        errors(br#""\q \x80 \u{110000}""#),
+        vec![
+            InvalidEscape {
+                span: Span::new(1, 3)
+            },
+            InvalidEscape {
+                span: Span::new(4, 6)
+            },
+            InvalidEscape {
+                span: Span::new(9, 11)
+            },
+        ]
+    );
+    assert_eq!(
+        errors(b"0x 0b102 1.5u8 10if 340282366920938463463374607431768211456"),
+        vec![
+            MalformedNumber {
+                span: Span::new(0, 2)
+            },
+            MalformedNumber {
+                span: Span::new(3, 8)
+            },
+            MalformedNumber {
+                span: Span::new(9, 14)
+            },
+            MalformedNumber {
+                span: Span::new(15, 19)
+            },
+            MalformedNumber {
+                span: Span::new(20, 59)
+            },
+        ]
+    );
+    assert_eq!(
+        errors(b"a\x07b"),
+        vec![ControlCharacter {
+            byte: 7,
+            span: Span::new(1, 2)
+        }]
+    );
+    assert_eq!(
+        errors(b"a \xff b"),
+        vec![InvalidUtf8 {
+            span: Span::new(2, 3)
+        }]
+    );
+    assert_eq!(
+        errors("@#$ é".as_bytes()),
+        vec![
+            UnexpectedCharacter {
+                character: '@',
+                span: Span::new(0, 1)
+            },
+            UnexpectedCharacter {
+                character: '#',
+                span: Span::new(1, 2)
+            },
+            UnexpectedCharacter {
+                character: '$',
+                span: Span::new(2, 3)
+            },
+            UnexpectedCharacter {
+                character: 'é',
+                span: Span::new(4, 6)
+            },
+        ]
+    );
+
+    // everything around an error is still lexed
+    let (lexed, errors) = Lexeme::lex(b"let @ x", 0);
+    assert_eq!(lexed.len(), 2);
+    assert_eq!(errors.len(), 1);
+}
+
+#[test]
+fn test_every_keyword() {
+    let code = "fn let if else struct enum mut match true false return while loop for in \
+                break continue impl trait pub use mod const as self Self";
+
+    for (word, lexeme) in code
+        .split_whitespace()
+        .zip(Lexeme::from_literal(code.as_bytes()).unwrap())
+    {
+        match lexeme.kind {
+            LexemeKind::VariableByteToken(keyword) => assert_eq!(keyword.as_str(), word),
+            kind => panic!("expected `{}` to be a keyword, found {:?}", word, kind),
+        }
+    }
+
+    // keywords are case sensitive and need the whole word
+    assert_eq!(
+        Lexeme::from_literal(b"selfish Mut self_")
+            .unwrap()
+            .into_iter()
+            .map(|lexeme| lexeme.kind)
+            .collect::<Vec<_>>(),
+        vec![
+            LexemeKind::Identifier(Symbol::intern("selfish")),
+            LexemeKind::Identifier(Symbol::intern("Mut")),
+            LexemeKind::Identifier(Symbol::intern("self_")),
+        ]
+    );
+}
+
+#[test]
+fn test_lexer_borrows_source() {
+    let code = String::from(r#"let name = "plain" @ "esc\n";"#);
+    let mut lexer = Lexer::new(&code);
+
+    lexer.next();
+    assert_eq!(
+        lexer.next().unwrap().unwrap().kind,
+        LexemeKind::Identifier(Symbol::intern("name"))
+    );
+
+    // errors come out in order between the lexemes around them
+    let rest: Vec<_> = lexer
+        .map(|lexeme| lexeme.map(|lexeme| lexeme.kind))
+        .collect();
+    assert!(matches!(
+        rest[1],
+        Ok(LexemeKind::Str(Cow::Borrowed("plain")))
+    ));
+    assert_eq!(
+        rest[2],
+        Err(LexError::UnexpectedCharacter {
+            character: '@',
+            span: Span::new(19, 20),
+        })
+    );
+    assert!(matches!(&rest[3], Ok(LexemeKind::Str(Cow::Owned(string))) if string == "esc\n"));
+    assert_eq!(rest.len(), 5);
+}
+
+#[test]
+fn test_lossless() {
+    let code = "//! file docs\n\
+        fn main() { // trailing\n\
+        \tlet x = 1 /* block\n comment */ + @2;\r\n\
+        \n\
+        }   \n\
+        /* at the end */";
+
+    let (lossless, errors) = Lexeme::lex_lossless(code, 0);
+    assert_eq!(lossless.to_string(), code);
+    assert_eq!(errors.len(), 1);
+
+    let brace = &lossless.lexemes[5];
+    assert_eq!(brace.text, "{");
+    assert_eq!(
+        brace
+            .trailing
+            .iter()
+            .map(|trivia| trivia.kind)
+            .collect::<Vec<_>>(),
+        vec![
+            TriviaKind::Whitespace,
+            TriviaKind::LineComment,
+            TriviaKind::Newline
+        ]
+    );
+
+    let two = lossless
+        .lexemes
+        .iter()
+        .find(|lexeme| lexeme.text == "2")
+        .unwrap();
+    assert_eq!(two.leading[0].kind, TriviaKind::Skipped);
+    assert_eq!(two.leading[0].text, "@");
+    assert_eq!(lossless.trailing[0].kind, TriviaKind::BlockComment);
+
+    // a file without any lexemes still round trips
+    for code in ["", "  \n", "// only a comment", "\u{1F600}"] {
+        assert_eq!(Lexeme::lex_lossless(code, 0).0.to_string(), code);
+    }
+}
+
+#[test]
+fn test_relex() {
+    let code = "/// docs\nfn main(s: String) {\n    let x = 0x1f + 1.5e3; /* a\n comment */\n    \"str\\n\" 'c' y..=z\n}\n";
+    let old = Lexeme::lex(code.as_bytes(), 10).0;
+
+    // every small edit at every position relexes to what a full lex gives
+    for start in 0..=code.len() {
+        for len in 0..3.min(code.len() - start + 1) {
+            for text in [
+                "", "a", "1", "\"", "*/", "/*", "\n", "'", "//", ".", "=", " ",
+            ] {
+                if !code.is_char_boundary(start + len) {
+                    continue;
+                }
+
+                let edit = TextEdit {
+                    span: Span::new(10 + start, 10 + start + len),
+                    text: text.to_string(),
+                };
+                let src = edit.apply(code, 10).unwrap();
+
+                assert_eq!(
+                    Lexeme::relex(&old, &edit, &src, 10).unwrap().0,
+                    Lexeme::lex(src.as_bytes(), 10).0,
+                    "{:?}",
+                    src
+                );
+            }
+        }
+    }
+
+    // lexing stops once it's back in sync, the bad input later on isn't
+    // looked at again
+    let code = "let x = 1;\nlet y = 2;\n@";
+    let (old, errors) = Lexeme::lex(code.as_bytes(), 0);
+    assert_eq!(errors.len(), 1);
+
+    let edit = TextEdit {
+        span: Span::new(8, 9),
+        text: "10".to_string(),
+    };
+    let src = edit.apply(code, 0).unwrap();
+    let (lexemes, errors) = Lexeme::relex(&old, &edit, &src, 0).unwrap();
+
+    assert!(errors.is_empty());
+    assert_eq!(lexemes, Lexeme::lex(src.as_bytes(), 0).0);
+
+    // typing at the very end of the buffer
+    let code = "let é = 1";
+    let old = Lexeme::lex(code.as_bytes(), 0).0;
+    let edit = |start: usize, end: usize| TextEdit {
+        span: Span::new(start, end),
+        text: ";".to_string(),
+    };
+    let src = edit(code.len(), code.len()).apply(code, 0).unwrap();
+    assert_eq!(src, "let é = 1;");
+    assert_eq!(
+        Lexeme::relex(&old, &edit(code.len(), code.len()), &src, 0)
+            .unwrap()
+            .0,
+        Lexeme::lex(src.as_bytes(), 0).0
+    );
+
+    // spans past the end, backwards or inside the `é` are errors, not panics
+    for (start, end) in [(code.len(), code.len() + 1), (3, 2), (5, 5)] {
+        let invalid = LexError::InvalidEdit {
+            span: Span::new(start, end),
+        };
+
+        assert_eq!(edit(start, end).apply(code, 0).unwrap_err(), invalid);
+        assert_eq!(
+            Lexeme::relex(&old, &edit(start, end), code, 0).unwrap_err(),
+            invalid
+        );
+    }
+    assert!(edit(0, 1).apply(code, 10).is_err());
+    assert!(Lexeme::relex(&old, &edit(0, 1), code, 0).is_err());
+}
+
+#[test]
+fn test_labels() {
+    use LexemeKind::*;
+
+    assert_eq!(
+        lex("'outer: loop { break 'outer; }"),
+        vec![
+            Label(Symbol::intern("outer")),
+            ByteToken(Token::Colon),
+            VariableByteToken(VarToken::Loop),
+            ByteToken(Token::OpenCurly),
+            VariableByteToken(VarToken::Break),
+            Label(Symbol::intern("outer")),
+            ByteToken(Token::Semicolon),
+            ByteToken(Token::CloseCurly),
+        ]
+    );
+    assert_eq!(lex("'a' 'a"), vec![Char('a'), Label(Symbol::intern("a"))]);
+    assert_eq!(lex("'_x"), vec![Label(Symbol::intern("_x"))]);
+}
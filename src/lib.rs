@@ -0,0 +1,6 @@
+pub mod check;
+pub mod lexer;
+pub mod span;
+pub mod symbol;
+pub mod tree;
+pub mod types;
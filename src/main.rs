@@ -1,18 +1,78 @@
-use crate::check::Error;
+use std::process::ExitCode;
 
-use check::type_and_fn_call_check;
-use lexer::Lexeme;
+use dyna_lang::check::type_and_fn_call_check;
+use dyna_lang::lexer::Lexer;
+use dyna_lang::span::SourceMap;
+use dyna_lang::tree;
 
-mod lexer;
-mod tree;
-mod check;
-
-fn main() -> Result<(), Error> {
+fn main() -> ExitCode {
     let code = "
     fn main(string: String) {
-        let array = [0, 1, 2];
+        greet(string);
+
+        {
+            greet(String::from(\"nested\"))
+        };
+    }
+
+    fn greet(name: String) -> String {
+        let greeting = if true { name } else { String::from(\"stranger\") };
+
+        greeting
+    }
+
+    fn area(width: u32, height: u32) -> u32 {
+        let mut total = width * height;
+        total += (width - 1) * 2;
+        let array: &[u8] = [1, 2, 3, 4];
+        let mut buffer = [0u8; 16];
+        buffer[0] = array[1];
+
+        for i in 0..height {
+            total += i;
+        }
+
+        let mut steps = 0;
+        let first = 'search: loop {
+            while steps < width {
+                if steps * steps > total {
+                    break 'search steps;
+                }
+                steps += 1;
+            }
+            break 0;
+        };
+
+        if width > height {
+            total += 1;
+        } else if width == height {
+            total = 0;
+        }
+
+        total + first(array, 0) as u32
+    }
+
+    fn first<T>(items: &[T], fallback: T) -> T {
+        if items.len() > 0 { items[0] } else { fallback }
+    }
 
-        if true { test } else {};
+    fn describe(option: Option) -> String {
+        match option {
+            Option::Some(string) => greet(string),
+            Option::None => String::from(\"nothing\"),
+        }
+    }
+
+    fn widen(rect: Rect, by: u32) -> Rect {
+        Rect {
+            width: rect.width + by,
+            ..rect
+        }
+    }
+
+    struct Rect {
+        width: u32,
+        height: u32,
     }
 
     struct X {
@@ -25,18 +85,46 @@ fn main() -> Result<(), Error> {
         None,
     }
 
+    enum ParseError {
+        Eof,
+        ParseFailed(String, u32),
+        Unexpected { line: u32, column: u32 },
+    }
+
+    fn fail(reason: String) -> ParseError {
+        ParseError::ParseFailed(reason, 1)
+    }
+
     main(String)
     ";
 
-    let bytes = code.as_bytes().to_vec();
+    let mut source_map = SourceMap::new();
+    let file = source_map.add_file("main.dy", code);
+
+    // the parser pulls lexemes as it goes, lexer errors come back with the
+    // parse errors
+    let (program, errors) = tree::parse(Lexer::from_file(source_map.file(file)));
+
+    for error in &errors {
+        if let Some(location) = source_map.lookup(error.span()) {
+            eprintln!("{}: {}", location, error);
+        }
+    }
 
-    let lexed = Lexeme::from_literal(bytes);
-    // println!("{:?}", lexed);
-    let tree = tree::tree(lexed);
+    println!("AST: {:#?}", program);
 
-    println!("AST: {:#?}", tree);
+    // the well formed items are still checked when some failed to parse
+    if let Err(error) = type_and_fn_call_check(&program.items) {
+        if let Some(location) = source_map.lookup(error.span()) {
+            eprintln!("{}: {:?}", location, error);
+        }
 
-    type_and_fn_call_check(tree)?;
+        return ExitCode::FAILURE;
+    }
 
-    Ok(())
+    if errors.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
 }
@@ -0,0 +1,186 @@
+//! Source positions
+//!
+//! Every lexeme and syntax tree node carries a `Span`, a half open range of
+//! byte offsets. Offsets are global to a `SourceMap`, each file added to the
+//! map gets its own region so a span alone is enough to find the file, line
+//! and column it came from.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    // smallest span covering both `self` and `other`
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId(usize);
+
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub src: String,
+    // global offset of the first byte of this file
+    pub start_pos: usize,
+    // byte offsets (relative to the file) where each line begins
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    fn new(name: String, src: String, start_pos: usize) -> SourceFile {
+        let line_starts = std::iter::once(0)
+            .chain(
+                src.bytes()
+                    .enumerate()
+                    .filter(|(_, b)| *b == b'\n')
+                    .map(|(i, _)| i + 1),
+            )
+            .collect();
+
+        SourceFile {
+            name,
+            src,
+            start_pos,
+            line_starts,
+        }
+    }
+
+    pub fn end_pos(&self) -> usize {
+        self.start_pos + self.src.len()
+    }
+
+    fn contains(&self, pos: usize) -> bool {
+        // the end position is included so spans pointing at EOF still resolve
+        self.start_pos <= pos && pos <= self.end_pos()
+    }
+
+    // 1 based line and column, columns count chars not bytes
+    fn line_col(&self, pos: usize) -> (usize, usize) {
+        let offset = pos - self.start_pos;
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let line_start = self.line_starts[line];
+        let column = match self.src.get(line_start..offset) {
+            Some(prefix) => prefix.chars().count(),
+            // offset is in the middle of a multi byte char
+            None => offset - line_start,
+        };
+
+        (line + 1, column + 1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location<'a> {
+    pub file: &'a str,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Location<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap::default()
+    }
+
+    pub fn add_file(&mut self, name: impl Into<String>, src: impl Into<String>) -> FileId {
+        // leave a one byte gap between files so the EOF position of one
+        // file is never the first byte of the next
+        let start_pos = self.files.last().map_or(0, |file| file.end_pos() + 1);
+
+        self.files
+            .push(SourceFile::new(name.into(), src.into(), start_pos));
+
+        FileId(self.files.len() - 1)
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0]
+    }
+
+    pub fn lookup_file(&self, pos: usize) -> Option<&SourceFile> {
+        self.files.iter().find(|file| file.contains(pos))
+    }
+
+    // location of the first byte of `span`
+    pub fn lookup(&self, span: Span) -> Option<Location<'_>> {
+        let file = self.lookup_file(span.start)?;
+        let (line, column) = file.line_col(span.start);
+
+        Some(Location {
+            file: &file.name,
+            line,
+            column,
+        })
+    }
+
+    // source text covered by `span`
+    pub fn snippet(&self, span: Span) -> Option<&str> {
+        let file = self.lookup_file(span.start)?;
+
+        file.src
+            .get(span.start - file.start_pos..span.end.checked_sub(file.start_pos)?)
+    }
+}
+
+#[test]
+fn test_lookup_line_and_column() {
+    let mut map = SourceMap::new();
+    let main = map.add_file("main.dy", "fn main() {\n    call(x)\n}\n");
+    let other = map.add_file("other.dy", "struct X {}");
+
+    let start = map.file(main).start_pos;
+    let call = Span::new(start + 16, start + 20);
+
+    assert_eq!(
+        map.lookup(call),
+        Some(Location {
+            file: "main.dy",
+            line: 2,
+            column: 5
+        })
+    );
+    assert_eq!(map.snippet(call), Some("call"));
+
+    let start = map.file(other).start_pos;
+
+    assert_eq!(
+        map.lookup(Span::new(start + 7, start + 8))
+            .map(|l| l.to_string()),
+        Some("other.dy:1:8".to_string())
+    );
+}
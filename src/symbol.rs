@@ -0,0 +1,82 @@
+//! Interned names
+//!
+//! Identifiers are interned as they're lexed, every name in the syntax tree
+//! is a `Symbol`, a small copyable id, so comparing and hashing names never
+//! looks at their text.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{LazyLock, Mutex};
+
+// The interner is global rather than owned by a parse so that a `Symbol`
+// can print itself anywhere, in errors and `Debug` output, without an
+// interner being passed around. The cost is that every distinct name ever
+// seen stays leaked for the life of the process, a long running service
+// parsing untrusted input grows by the set of distinct names it's been
+// sent (repeating a name costs nothing), and `intern`, `as_str` and
+// printing all take one global lock. Compare symbols rather than their
+// text on hot paths.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+// the text of every symbol handed out, interned strings are never freed so
+// `as_str` can give out `&'static str`s
+#[derive(Default)]
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+static INTERNER: LazyLock<Mutex<Interner>> = LazyLock::new(Mutex::default);
+
+fn interner() -> std::sync::MutexGuard<'static, Interner> {
+    // the interner is only ever appended to, so a panic while it was
+    // locked can't have left it half updated
+    INTERNER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl Symbol {
+    // the same name always gives back the same symbol
+    pub fn intern(name: &str) -> Symbol {
+        let mut interner = interner();
+
+        if let Some(symbol) = interner.symbols.get(name) {
+            return *symbol;
+        }
+
+        let name: &'static str = Box::leak(name.into());
+        let symbol = Symbol(interner.names.len() as u32);
+        interner.names.push(name);
+        interner.symbols.insert(name, symbol);
+
+        symbol
+    }
+
+    pub fn as_str(self) -> &'static str {
+        interner().names[self.0 as usize]
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[test]
+fn test_intern() {
+    let main = Symbol::intern("main");
+
+    assert_eq!(main, Symbol::intern(&String::from("main")));
+    assert_ne!(main, Symbol::intern("Main"));
+    assert_eq!(main.as_str(), "main");
+    assert_eq!(format!("{:?}", main), "\"main\"");
+}
@@ -1,266 +1,2967 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::fmt;
 
-use crate::lexer::{Lexeme, Token, VarToken};
+use crate::lexer::{
+    DocComment, DocStyle, FloatLiteral, IntLiteral, LexError, Lexeme, LexemeKind, Token, VarToken,
+};
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::types::Type;
 
+// `{ stmt; stmt; tail }`, the block's value is its tail expression or unit
+// when there isn't one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block<'src> {
+    pub stmts: Vec<Stmt<'src>>,
+    pub tail: Option<Box<Expr<'src>>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt<'src> {
+    // `let pattern: Type = init;`
+    Let {
+        pattern: Pattern<'src>,
+        let_type: Option<Type>,
+        init: Expr<'src>,
+        span: Span,
+    },
+    // `expr;`, or a block-like expression such as `{ ... }` that doesn't
+    // need the `;`
+    Expr(Expr<'src>),
+}
+
+// the shape a value is matched against, and the names bound to its parts
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern<'src> {
+    // `_`, matches anything and binds nothing
+    Wildcard {
+        span: Span,
+    },
+    // `name` or `mut name`
+    Binding {
+        name: Symbol,
+        mutable: bool,
+        span: Span,
+    },
+    // `1`, `"text"`, `'c'` or `true`
+    Literal {
+        value: Literal<'src>,
+        span: Span,
+    },
+    // `Enum::Variant`
+    Path {
+        segments: Vec<Symbol>,
+        span: Span,
+    },
+    // `Enum::Variant(a, b)`
+    TupleStruct {
+        path: Vec<Symbol>,
+        elements: Vec<Pattern<'src>>,
+        span: Span,
+    },
+    // `Struct { field, other: pattern, .. }`
+    Struct {
+        path: Vec<Symbol>,
+        fields: Vec<FieldPattern<'src>>,
+        // whether the fields not named are skipped with `..`
+        rest: bool,
+        span: Span,
+    },
+    // `A | B`
+    Or {
+        alternatives: Vec<Pattern<'src>>,
+        span: Span,
+    },
+}
+
+impl Pattern<'_> {
+    pub fn span(&self) -> Span {
+        match self {
+            Pattern::Wildcard { span }
+            | Pattern::Binding { span, .. }
+            | Pattern::Literal { span, .. }
+            | Pattern::Path { span, .. }
+            | Pattern::TupleStruct { span, .. }
+            | Pattern::Struct { span, .. }
+            | Pattern::Or { span, .. } => *span,
+        }
+    }
+}
+
+// `field: pattern` in a struct pattern, `field` on its own is short for
+// `field: field`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldPattern<'src> {
+    pub name: Symbol,
+    pub pattern: Pattern<'src>,
+    pub span: Span,
+}
+
+// `field: value` in a struct literal
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldInit<'src> {
+    pub name: Symbol,
+    pub value: Expr<'src>,
+    pub span: Span,
+}
+
+// `pattern if guard => body`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm<'src> {
+    pub pattern: Pattern<'src>,
+    pub guard: Option<Expr<'src>>,
+    pub body: Expr<'src>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal<'src> {
+    Int(IntLiteral<'src>),
+    Float(FloatLiteral<'src>),
+    Str(Cow<'src, str>),
+    Char(char),
+    Bool(bool),
+    // `()`
+    Unit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr<'src> {
+    Literal {
+        value: Literal<'src>,
+        span: Span,
+    },
+    // `name` or `Enum::Variant`
+    Path {
+        segments: Vec<Symbol>,
+        span: Span,
+    },
+    // `-x` or `!x`
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr<'src>>,
+        span: Span,
+    },
+    // `lhs op rhs`
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr<'src>>,
+        rhs: Box<Expr<'src>>,
+        span: Span,
+    },
+    // `value as Type`
+    Cast {
+        value: Box<Expr<'src>>,
+        cast_type: Type,
+        span: Span,
+    },
+    // `callee(args, ...)`
+    Call {
+        callee: Box<Expr<'src>>,
+        args: Vec<Expr<'src>>,
+        span: Span,
+    },
+    // `receiver.method(args, ...)`
+    MethodCall {
+        receiver: Box<Expr<'src>>,
+        method: Symbol,
+        args: Vec<Expr<'src>>,
+        span: Span,
+    },
+    // `base.field`
+    Field {
+        base: Box<Expr<'src>>,
+        field: Symbol,
+        span: Span,
+    },
+    // `base[index]`
+    Index {
+        base: Box<Expr<'src>>,
+        index: Box<Expr<'src>>,
+        span: Span,
+    },
+    // `Path { field: value, field, ..base }`
+    Struct {
+        path: Vec<Symbol>,
+        fields: Vec<FieldInit<'src>>,
+        // the struct the fields not given are taken from
+        base: Option<Box<Expr<'src>>>,
+        span: Span,
+    },
+    // `[a, b, c]`
+    Array {
+        elements: Vec<Expr<'src>>,
+        span: Span,
+    },
+    // `[value; len]`, `len` copies of `value`
+    Repeat {
+        value: Box<Expr<'src>>,
+        len: Box<Expr<'src>>,
+        span: Span,
+    },
+    // `target = value` or `target op= value`
+    Assign {
+        op: Option<BinaryOp>,
+        target: Box<Expr<'src>>,
+        value: Box<Expr<'src>>,
+        span: Span,
+    },
+    Block(Block<'src>),
+    // `if condition { ... } else { ... }`, the else branch is either a
+    // block or another `if` for `else if`
+    If {
+        condition: Box<Expr<'src>>,
+        then_block: Block<'src>,
+        else_branch: Option<Box<Expr<'src>>>,
+        span: Span,
+    },
+    // `match scrutinee { arm, ... }`
+    Match {
+        scrutinee: Box<Expr<'src>>,
+        arms: Vec<MatchArm<'src>>,
+        span: Span,
+    },
+    // `'label: while condition { ... }`
+    While {
+        label: Option<Symbol>,
+        condition: Box<Expr<'src>>,
+        body: Block<'src>,
+        span: Span,
+    },
+    // `'label: loop { ... }`, its value is whatever `break` gives it
+    Loop {
+        label: Option<Symbol>,
+        body: Block<'src>,
+        span: Span,
+    },
+    // `'label: for pattern in iterable { ... }`
+    For {
+        label: Option<Symbol>,
+        pattern: Pattern<'src>,
+        iterable: Box<Expr<'src>>,
+        body: Block<'src>,
+        span: Span,
+    },
+    // `break 'label value`, both optional
+    Break {
+        label: Option<Symbol>,
+        value: Option<Box<Expr<'src>>>,
+        span: Span,
+    },
+    // `continue 'label`
+    Continue {
+        label: Option<Symbol>,
+        span: Span,
+    },
+    // `start..end` or `start..=end`, either end can be left off an
+    // exclusive range
+    Range {
+        start: Option<Box<Expr<'src>>>,
+        end: Option<Box<Expr<'src>>>,
+        inclusive: bool,
+        span: Span,
+    },
+    // `return` or `return value`
+    Return {
+        value: Option<Box<Expr<'src>>>,
+        span: Span,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    // `-`
+    Neg,
+    // `!`
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    fn from_token(token: &Token) -> Option<BinaryOp> {
+        match token {
+            Token::Plus => Some(BinaryOp::Add),
+            Token::Minus => Some(BinaryOp::Sub),
+            Token::Star => Some(BinaryOp::Mul),
+            Token::Slash => Some(BinaryOp::Div),
+            Token::Percent => Some(BinaryOp::Rem),
+            Token::EqEq => Some(BinaryOp::Eq),
+            Token::NotEq => Some(BinaryOp::Ne),
+            Token::Lt => Some(BinaryOp::Lt),
+            Token::LtEq => Some(BinaryOp::Le),
+            Token::Gt => Some(BinaryOp::Gt),
+            Token::GtEq => Some(BinaryOp::Ge),
+            Token::AndAnd => Some(BinaryOp::And),
+            Token::OrOr => Some(BinaryOp::Or),
+            _ => None,
+        }
+    }
+
+    // how tightly the operator holds on to the operands on its left and
+    // right, a right power above the left makes it left associative
+    fn binding_power(self) -> (u8, u8) {
+        match self {
+            BinaryOp::Or => (1, 2),
+            BinaryOp::And => (3, 4),
+            BinaryOp::Eq
+            | BinaryOp::Ne
+            | BinaryOp::Lt
+            | BinaryOp::Le
+            | BinaryOp::Gt
+            | BinaryOp::Ge => (5, 6),
+            BinaryOp::Add | BinaryOp::Sub => (7, 8),
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => (9, 10),
+        }
+    }
+
+    pub fn is_comparison(self) -> bool {
+        self.binding_power().0 == 5
+    }
+
+    pub fn is_logical(self) -> bool {
+        matches!(self, BinaryOp::And | BinaryOp::Or)
+    }
+}
+
+// the parser recurses into nested expressions, types and patterns, past
+// this many levels the input is rejected rather than overflowing the stack.
+// A debug build parsing and checking this deep fits in a 2MB thread stack
+const MAX_NESTING: usize = 64;
+
+// `as` binds tighter than any binary operator but looser than unary ones,
+// `-x as u8` is `(-x) as u8`
+const CAST_POWER: u8 = 11;
+
+impl Expr<'_> {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Literal { span, .. }
+            | Expr::Path { span, .. }
+            | Expr::Unary { span, .. }
+            | Expr::Binary { span, .. }
+            | Expr::Cast { span, .. }
+            | Expr::Call { span, .. }
+            | Expr::MethodCall { span, .. }
+            | Expr::Field { span, .. }
+            | Expr::Index { span, .. }
+            | Expr::Struct { span, .. }
+            | Expr::Array { span, .. }
+            | Expr::Repeat { span, .. }
+            | Expr::Assign { span, .. }
+            | Expr::If { span, .. }
+            | Expr::Match { span, .. }
+            | Expr::While { span, .. }
+            | Expr::Loop { span, .. }
+            | Expr::For { span, .. }
+            | Expr::Break { span, .. }
+            | Expr::Continue { span, .. }
+            | Expr::Range { span, .. }
+            | Expr::Return { span, .. } => *span,
+            Expr::Block(block) => block.span,
+        }
+    }
+
+    // expressions ending in a `}` are statements on their own without a `;`
+    pub fn is_block_like(&self) -> bool {
+        matches!(
+            self,
+            Expr::Block(_)
+                | Expr::If { .. }
+                | Expr::Match { .. }
+                | Expr::While { .. }
+                | Expr::Loop { .. }
+                | Expr::For { .. }
+        )
+    }
+}
+
+// `mut name: Type` in a function's parameter list
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct DynaType {
-    type_name: String,
+pub struct Param {
+    pub name: Symbol,
+    pub param_type: Type,
+    pub mutable: bool,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
-struct Block {
-    block: Vec<Lexeme>,
+pub struct DynaFunction<'src> {
+    // text of the `///` comments written above the item
+    pub docs: Vec<String>,
+    pub name: Symbol,
+    // the `T` and `U` of `fn name<T, U>`
+    pub generics: Vec<Symbol>,
+    pub body: Block<'src>,
+    // parameters in the order they're declared
+    pub params: Vec<Param>,
+    // the type after `->`, unit when there isn't one
+    pub return_type: Type,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
-pub struct DynaFunction {
-    pub name: String,
-    body: Block,
-    pub signature: HashMap<String, DynaType>,
+pub struct DynaEnum {
+    // text of the `///` comments written above the item
+    pub docs: Vec<String>,
+    pub name: Symbol,
+    // in declaration order
+    pub variants: Vec<Variant>,
+    pub span: Span,
 }
 
-#[derive(Debug, Clone)]
-struct DynaEnum {
-    name: String,
-    fields: HashMap<String, Option<DynaType>>,
+impl DynaEnum {
+    pub fn variant(&self, name: Symbol) -> Option<&Variant> {
+        self.variants.iter().find(|variant| variant.name == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    pub name: Symbol,
+    pub fields: VariantFields,
+    pub span: Span,
+}
+
+// what a variant carries, fields are kept in declaration order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VariantFields {
+    // `None`
+    Unit,
+    // `ParseFailed(String, u32)`
+    Tuple(Vec<Type>),
+    // `Moved { x: u32, y: u32 }`
+    Struct(Vec<(Symbol, Type)>),
 }
 
 #[derive(Debug, Clone)]
-struct DynaStruct {
-    name: String,
-    fields: HashMap<String, DynaType>,
+pub struct DynaStruct {
+    // text of the `///` comments written above the item
+    pub docs: Vec<String>,
+    pub name: Symbol,
+    // in declaration order
+    pub fields: Vec<(Symbol, Type)>,
+    pub span: Span,
+}
+
+impl DynaStruct {
+    pub fn field(&self, name: Symbol) -> Option<&Type> {
+        self.fields
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, field_type)| field_type)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct DynaCall {
-    pub fn_name: String,
-    pub signature: Vec<DynaType>,
+    pub fn_name: Symbol,
+    pub signature: Vec<Type>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
-pub enum Ast {
+pub enum Ast<'src> {
     Structure(DynaStruct),
     Enumeration(DynaEnum),
-    Function(DynaFunction),
+    Function(DynaFunction<'src>),
     FunctionCall(DynaCall),
-    Block(Box<Ast>),
-    Genesis,
 }
 
-pub fn tree(lexemes: Vec<Lexeme>) -> Vec<Ast> {
-    let mut tree = vec![Ast::Block(Box::new(Ast::Genesis))];
-    let mut peekable = lexemes.iter().peekable();
+// the top level items of a file in the order they were written
+#[derive(Debug, Clone, Default)]
+pub struct Program<'src> {
+    // text of the `//!` comments in the file
+    pub docs: Vec<String>,
+    pub items: Vec<Ast<'src>>,
+}
+
+// what the parser was looking for when it hit an error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expected {
+    Token(Token),
+    Keyword(VarToken),
+    // `fn`, `struct`, `enum` or a call at the top level
+    Item,
+    // function, argument, struct, field, enum or variant name
+    Name,
+    Type,
+    Expression,
+    // the length of an array type
+    Length,
+    Pattern,
+    // `while`, `loop` or `for` after a label
+    Loop,
+}
 
-    while let Some(token) = peekable.peek() {
-        match token {
-            Lexeme::VariableByteToken(var_token) => match var_token {
-                VarToken::Fn => {
-                    peekable.next();
-                    if let Some(Lexeme::TokenLiteral(fn_name)) = peekable.peek() {
-                        peekable.next();
-                        let mut signature = HashMap::new();
-                        if let Some(Lexeme::ByteToken(Token::OpenParen)) = peekable.peek() {
-                            peekable.next();
-                            if let Some(Lexeme::TokenLiteral(argument)) = peekable.peek() {
-                                peekable.next();
-
-                                if let Some(Lexeme::ByteToken(Token::Colon)) = peekable.peek() {
-                                    peekable.next();
-                                } else {
-                                    panic!("bad function syntax no colon after arg name");
-                                }
-
-                                if let Some(Lexeme::TokenLiteral(type_arg)) = peekable.peek() {
-                                    peekable.next();
-                                    let argument =
-                                        std::str::from_utf8(argument).unwrap().to_string();
-                                    let type_name =
-                                        std::str::from_utf8(type_arg).unwrap().to_string();
-
-                                    signature.insert(argument, DynaType { type_name });
-                                } else {
-                                    panic!("bad function syntax no arg type after arg name");
-                                }
-                            }
-                            assert_eq!(
-                                peekable.next(),
-                                Some(&Lexeme::ByteToken(Token::CloseParen))
-                            );
-                        } else {
-                            panic!("bad function syntax no open paren after fn name");
-                        }
-
-                        if let Some(Lexeme::ByteToken(Token::OpenCurly)) = peekable.peek() {
-                            peekable.next();
-                            let mut block = Vec::new();
-
-                            while Some(&&Lexeme::ByteToken(Token::CloseCurly)) != peekable.peek() {
-                                if let Some(x) = peekable.next() {
-                                    block.push(x.clone())
-                                }
-                            }
-
-                            let name = std::str::from_utf8(fn_name).unwrap().to_string();
-
-                            let functor = DynaFunction {
-                                name,
-                                body: Block { block },
-                                signature,
-                            };
-
-                            tree.push(Ast::Function(functor));
-                        }
-                    }
-                }
-                VarToken::Struct => {
-                    peekable.next();
-                    if let Some(Lexeme::TokenLiteral(struct_name)) = peekable.peek() {
-                        peekable.next();
-                        if let Some(Lexeme::ByteToken(Token::OpenCurly)) = peekable.peek() {
-                            peekable.next();
-
-                            let mut struct_fields = HashMap::new();
-
-                            while let Some(Lexeme::TokenLiteral(struct_field)) = peekable.peek() {
-                                let field_name = flush_whitespace(struct_field);
-                                peekable.next();
-
-                                if let Some(Lexeme::ByteToken(Token::Colon)) = peekable.peek() {
-                                    peekable.next();
-                                    if let Some(Lexeme::TokenLiteral(struct_field_type)) =
-                                        peekable.peek()
-                                    {
-                                        peekable.next();
-                                        let type_name = flush_whitespace(struct_field_type);
-
-                                        struct_fields.insert(field_name, DynaType { type_name });
-
-                                        if let Some(Lexeme::ByteToken(Token::Comma)) =
-                                            peekable.peek()
-                                        {
-                                            peekable.next();
-
-                                            continue;
-                                        } else {
-                                            break;
-                                        }
-                                    }
-                                }
-                            }
-
-                            assert_eq!(
-                                peekable.next(),
-                                Some(&Lexeme::ByteToken(Token::CloseCurly))
-                            );
-
-                            let name = flush_whitespace(struct_name);
-
-                            let structure = DynaStruct {
-                                name,
-                                fields: struct_fields,
-                            };
-
-                            tree.push(Ast::Structure(structure));
-                        }
-                    }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    // the next lexeme isn't what the grammar allows here
+    Expected {
+        expected: Expected,
+        // the lexeme that was found, as it's shown in the error message
+        found: String,
+        span: Span,
+    },
+    // ran out of lexemes in the middle of an item
+    UnexpectedEof {
+        expected: Expected,
+        span: Span,
+    },
+    // a keyword used where a name is expected, `fn match()`
+    ReservedWord {
+        keyword: VarToken,
+        span: Span,
+    },
+    // `struct S { a: u8, a: u16 }`, the span is the second `a`
+    DuplicateField {
+        name: Symbol,
+        span: Span,
+    },
+    // `enum E { A, A }`, the span is the second `A`
+    DuplicateVariant {
+        name: Symbol,
+        span: Span,
+    },
+    // `a < b < c`, comparisons don't associate
+    ChainedComparison {
+        span: Span,
+    },
+    // brackets, types, patterns or chains of operators nested deeper than
+    // `MAX_NESTING`
+    NestedTooDeeply {
+        span: Span,
+    },
+    // the lexer couldn't make a lexeme out of part of the input
+    Lex(LexError),
+}
+
+impl ParseError {
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::Expected { span, .. }
+            | ParseError::UnexpectedEof { span, .. }
+            | ParseError::ReservedWord { span, .. }
+            | ParseError::DuplicateField { span, .. }
+            | ParseError::DuplicateVariant { span, .. }
+            | ParseError::ChainedComparison { span }
+            | ParseError::NestedTooDeeply { span } => *span,
+            ParseError::Lex(error) => error.span(),
+        }
+    }
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Token(token) => write!(f, "`{}`", token.as_str()),
+            Expected::Keyword(keyword) => write!(f, "`{}`", keyword.as_str()),
+            Expected::Item => write!(f, "an item"),
+            Expected::Name => write!(f, "a name"),
+            Expected::Type => write!(f, "a type"),
+            Expected::Expression => write!(f, "an expression"),
+            Expected::Length => write!(f, "an array length"),
+            Expected::Pattern => write!(f, "a pattern"),
+            Expected::Loop => write!(f, "a loop"),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Expected {
+                expected, found, ..
+            } => write!(f, "expected {}, found {}", expected, found),
+            ParseError::UnexpectedEof { expected, .. } => {
+                write!(f, "expected {}, found end of file", expected)
+            }
+            ParseError::ReservedWord { keyword, .. } => {
+                write!(
+                    f,
+                    "`{}` is a reserved word and can't be used as a name",
+                    keyword.as_str()
+                )
+            }
+            ParseError::DuplicateField { name, .. } => {
+                write!(f, "field `{}` is declared more than once", name)
+            }
+            ParseError::DuplicateVariant { name, .. } => {
+                write!(f, "variant `{}` is declared more than once", name)
+            }
+            ParseError::ChainedComparison { .. } => {
+                write!(f, "comparison operators can't be chained")
+            }
+            ParseError::NestedTooDeeply { .. } => {
+                write!(f, "nested too deeply, at most {} levels", MAX_NESTING)
+            }
+            ParseError::Lex(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn tree<'src>(
+    lexemes: impl IntoIterator<Item = Result<Lexeme<'src>, LexError>>,
+) -> Result<Program<'src>, Vec<ParseError>> {
+    let (program, errors) = parse(lexemes);
+
+    if errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(errors)
+    }
+}
+
+// like `tree` but never gives up, a malformed item is recorded as an error
+// and skipped so the rest of the items still make it into the program.
+// Lexemes are pulled from `lexemes` as they're needed, usually a `Lexer`,
+// and its errors are reported in order with the parse errors
+pub fn parse<'src>(
+    lexemes: impl IntoIterator<Item = Result<Lexeme<'src>, LexError>>,
+) -> (Program<'src>, Vec<ParseError>) {
+    let mut parser = Parser {
+        lexemes: lexemes.into_iter(),
+        peeked: None,
+        end: 0,
+        prev_span: Span::default(),
+        depth: 0,
+        nesting: 0,
+        generics: Vec::new(),
+        no_struct_literal: false,
+        errors: Vec::new(),
+    };
+    let mut program = Program::default();
+
+    loop {
+        // `///` docs belong to the item after them, `//!` docs to the file
+        let mut docs = Vec::new();
+        while let Some(doc) = parser.doc_comment() {
+            match doc.style {
+                DocStyle::Outer => docs.push(doc.text.to_string()),
+                DocStyle::Inner => program.docs.push(doc.text.to_string()),
+            }
+        }
+
+        // a broken item is skipped up to the `}` that closes it
+        let item_depth = parser.depth;
+        let item = match parser.peek().map(|lexeme| &lexeme.kind) {
+            None => break,
+            Some(LexemeKind::VariableByteToken(VarToken::Fn)) => parser.function(docs),
+            Some(LexemeKind::VariableByteToken(VarToken::Struct)) => parser.structure(docs),
+            Some(LexemeKind::VariableByteToken(VarToken::Enum)) => parser.enumeration(docs),
+            Some(LexemeKind::Identifier(_)) => parser.call(),
+            Some(_) => Err(parser.unexpected(Expected::Item)),
+        };
+
+        match item {
+            Ok(item) => program.items.push(item),
+            Err(error) => {
+                parser.errors.push(error);
+                parser.synchronize(item_depth);
+            }
+        }
+    }
+
+    (program, parser.errors)
+}
+
+struct Parser<'src, I> {
+    lexemes: I,
+    // the next lexeme, pulled from `lexemes` when it's first looked at
+    peeked: Option<Lexeme<'src>>,
+    // end of the last lexeme pulled, where running out of input is reported
+    end: usize,
+    // span of the last lexeme consumed
+    prev_span: Span,
+    // how many `{` consumed so far haven't been closed
+    depth: usize,
+    // how many expressions, types and patterns the parser is inside of
+    nesting: usize,
+    // generic parameters of the function being parsed
+    generics: Vec<Symbol>,
+    // set while parsing an `if` condition or `match` scrutinee, where a `{`
+    // after a path opens the block rather than a struct literal
+    no_struct_literal: bool,
+    errors: Vec<ParseError>,
+}
+
+impl<'src, I> Parser<'src, I>
+where
+    I: Iterator<Item = Result<Lexeme<'src>, LexError>>,
+{
+    fn fill(&mut self) {
+        while self.peeked.is_none() {
+            match self.lexemes.next() {
+                Some(Ok(lexeme)) => {
+                    self.end = lexeme.span.end;
+                    self.peeked = Some(lexeme);
                 }
-                VarToken::Enum => {
-                    peekable.next();
-                    if let Some(Lexeme::TokenLiteral(enum_name)) = peekable.peek() {
-                        peekable.next();
-                        if let Some(Lexeme::ByteToken(Token::OpenCurly)) = peekable.peek() {
-                            peekable.next();
-
-                            let mut enum_fields = HashMap::new();
-
-                            while let Some(Lexeme::TokenLiteral(enum_field)) = peekable.peek() {
-                                let field_name = flush_whitespace(enum_field);
-                                peekable.next();
-
-                                if let Some(Lexeme::ByteToken(Token::OpenParen)) = peekable.peek() {
-                                    peekable.next();
-                                    if let Some(Lexeme::TokenLiteral(enum_field_type)) =
-                                        peekable.peek()
-                                    {
-                                        peekable.next();
-                                        let type_name = flush_whitespace(enum_field_type);
-
-                                        enum_fields.insert(field_name, Some(DynaType { type_name }));
-
-                                        assert_eq!(
-                                            peekable.next(),
-                                            Some(&Lexeme::ByteToken(Token::CloseParen))
-                                        );
-
-                                        if let Some(Lexeme::ByteToken(Token::Comma)) =
-                                            peekable.peek()
-                                        {
-                                            peekable.next();
-
-                                            continue;
-                                        } else {
-                                            break;
-                                        }
-                                    }
-                                } else {
-                                    enum_fields.insert(field_name, None);
-                                }
-                            }
-
-                            let syn_check = peekable.next();
-                            match syn_check {
-                                Some(&Lexeme::ByteToken(Token::CloseCurly))
-                                | Some(&Lexeme::ByteToken(Token::Comma)) => (),
-                                _ => panic!("expected comma or close curly"),
-                            };
-
-                            let name = flush_whitespace(enum_name);
-
-                            let enumeration = DynaEnum {
-                                name,
-                                fields: enum_fields,
-                            };
-
-                            tree.push(Ast::Enumeration(enumeration));
-                        }
-                    }
+                Some(Err(error)) => self.errors.push(ParseError::Lex(error)),
+                None => return,
+            }
+        }
+    }
+
+    // doc comments only mean something in front of an item, everywhere
+    // else they're skipped like any other comment
+    fn peek(&mut self) -> Option<&Lexeme<'src>> {
+        self.fill();
+        while let Some(LexemeKind::DocComment(_)) = self.peeked.as_ref().map(|l| &l.kind) {
+            self.peeked = None;
+            self.fill();
+        }
+
+        self.peeked.as_ref()
+    }
+
+    fn doc_comment(&mut self) -> Option<DocComment<'src>> {
+        self.fill();
+        match self.peeked.take() {
+            Some(Lexeme {
+                kind: LexemeKind::DocComment(doc),
+                ..
+            }) => Some(doc),
+            lexeme => {
+                self.peeked = lexeme;
+                None
+            }
+        }
+    }
+
+    fn bump(&mut self) -> Option<Lexeme<'src>> {
+        self.peek()?;
+        let lexeme = self.peeked.take()?;
+        self.prev_span = lexeme.span;
+
+        match lexeme.kind {
+            LexemeKind::ByteToken(Token::OpenCurly) => self.depth += 1,
+            LexemeKind::ByteToken(Token::CloseCurly) => self.depth = self.depth.saturating_sub(1),
+            _ => (),
+        }
+
+        Some(lexeme)
+    }
+
+    // whether the next lexeme closes the current list, running out of
+    // lexemes counts too so the caller reports the missing `close`
+    fn at_end_of(&mut self, close: Token) -> bool {
+        self.peek()
+            .is_none_or(|lexeme| lexeme.kind == LexemeKind::ByteToken(close))
+    }
+
+    // whether the next lexeme is `token`
+    fn at(&mut self, token: Token) -> bool {
+        self.peek()
+            .is_some_and(|lexeme| lexeme.kind == LexemeKind::ByteToken(token))
+    }
+
+    // whether the next lexeme starts an expression ending in a `}`
+    fn at_block_like(&mut self) -> bool {
+        matches!(
+            self.peek().map(|lexeme| &lexeme.kind),
+            Some(
+                LexemeKind::ByteToken(Token::OpenCurly)
+                    | LexemeKind::Label(_)
+                    | LexemeKind::VariableByteToken(
+                        VarToken::If
+                            | VarToken::Match
+                            | VarToken::While
+                            | VarToken::Loop
+                            | VarToken::For
+                    )
+            )
+        )
+    }
+
+    fn eat(&mut self, token: Token) -> Option<Span> {
+        let lexeme = self.peek()?;
+        if lexeme.kind != LexemeKind::ByteToken(token) {
+            return None;
+        }
+
+        self.bump().map(|lexeme| lexeme.span)
+    }
+
+    fn eat_keyword(&mut self, keyword: VarToken) -> Option<Span> {
+        let lexeme = self.peek()?;
+        if lexeme.kind != LexemeKind::VariableByteToken(keyword) {
+            return None;
+        }
+
+        self.bump().map(|lexeme| lexeme.span)
+    }
+
+    fn expect(&mut self, token: Token) -> Result<Span, ParseError> {
+        match self.eat(token.clone()) {
+            Some(span) => Ok(span),
+            None => Err(self.unexpected(Expected::Token(token))),
+        }
+    }
+
+    fn expect_identifier(&mut self, expected: Expected) -> Result<(Symbol, Span), ParseError> {
+        match self.peek() {
+            Some(Lexeme {
+                kind: LexemeKind::Identifier(name),
+                span,
+            }) => {
+                let name = (*name, *span);
+                self.bump();
+                Ok(name)
+            }
+            Some(Lexeme {
+                kind: LexemeKind::VariableByteToken(keyword),
+                span,
+            }) if expected == Expected::Name => Err(ParseError::ReservedWord {
+                keyword: keyword.clone(),
+                span: *span,
+            }),
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn expect_type(&mut self) -> Result<Type, ParseError> {
+        self.nested(Self::single_type)
+    }
+
+    // `Name`, `T`, `()`, `!`, `&T`, `&mut T`, `[T]`, `[T; N]`, `(A, B)` or
+    // `fn(A, B) -> R`
+    fn single_type(&mut self) -> Result<Type, ParseError> {
+        if self.eat(Token::Bang).is_some() {
+            return Ok(Type::Never);
+        }
+
+        if self.eat(Token::OpenParen).is_some() {
+            let mut elements = Vec::new();
+            let mut trailing_comma = false;
+
+            while !self.at_end_of(Token::CloseParen) {
+                elements.push(self.expect_type()?);
+                trailing_comma = self.eat(Token::Comma).is_some();
+
+                if !trailing_comma {
+                    break;
                 }
-                _ => {
-                    peekable.next();
+            }
+            self.expect(Token::CloseParen)?;
+
+            // `(T)` only groups, `(T,)` is a tuple of one
+            return match elements.len() {
+                0 => Ok(Type::Unit),
+                1 if !trailing_comma => Ok(elements.remove(0)),
+                _ => Ok(Type::Tuple(elements)),
+            };
+        }
+
+        // `&&T` is lexed as one `&&`
+        let refs = if self.eat(Token::Ampersand).is_some() {
+            1
+        } else if self.eat(Token::AndAnd).is_some() {
+            2
+        } else {
+            0
+        };
+        if refs > 0 {
+            let mutable = self.eat_keyword(VarToken::Mut).is_some();
+            let mut ty = Type::Ref {
+                mutable,
+                referent: Box::new(self.expect_type()?),
+            };
+
+            if refs == 2 {
+                ty = Type::Ref {
+                    mutable: false,
+                    referent: Box::new(ty),
+                };
+            }
+
+            return Ok(ty);
+        }
+
+        if self.eat(Token::OpenSquare).is_some() {
+            let element = Box::new(self.expect_type()?);
+
+            let array_type = if self.eat(Token::Semicolon).is_some() {
+                let len = match self.peek() {
+                    Some(Lexeme {
+                        kind: LexemeKind::Integer(len),
+                        ..
+                    }) => len.value(),
+                    _ => None,
+                };
+                let Some(len) = len else {
+                    return Err(self.unexpected(Expected::Length));
+                };
+                self.bump();
+
+                Type::Array { element, len }
+            } else {
+                Type::Slice(element)
+            };
+            self.expect(Token::CloseSquare)?;
+
+            return Ok(array_type);
+        }
+
+        if self.eat_keyword(VarToken::Fn).is_some() {
+            self.expect(Token::OpenParen)?;
+            let mut params = Vec::new();
+
+            while !self.at_end_of(Token::CloseParen) {
+                params.push(self.expect_type()?);
+
+                if self.eat(Token::Comma).is_none() {
+                    break;
                 }
+            }
+            self.expect(Token::CloseParen)?;
+
+            let ret = match self.eat(Token::Arrow) {
+                Some(_) => self.expect_type()?,
+                None => Type::Unit,
+            };
+
+            return Ok(Type::Fn {
+                params,
+                ret: Box::new(ret),
+            });
+        }
+
+        let (type_name, _) = self.expect_identifier(Expected::Type)?;
+
+        if self.generics.contains(&type_name) {
+            return Ok(Type::Param(type_name));
+        }
+
+        Ok(Type::from_symbol(type_name))
+    }
+
+    // error for whatever lexeme is next
+    fn unexpected(&mut self, expected: Expected) -> ParseError {
+        match self.peek() {
+            Some(lexeme) => ParseError::Expected {
+                expected,
+                found: lexeme.kind.to_string(),
+                span: lexeme.span,
             },
-            Lexeme::TokenLiteral(token) => {
-                peekable.next();
-                if let Some(Lexeme::ByteToken(Token::OpenParen)) = peekable.peek() {
-                    peekable.next();
-
-                    let mut types = Vec::new();
-                    if let Some(Lexeme::TokenLiteral(call_arg_type)) = peekable.peek() {
-                        peekable.next();
-                        types.push(DynaType {
-                            type_name: flush_whitespace(call_arg_type)
-                        });
-                    }
+            None => ParseError::UnexpectedEof {
+                expected,
+                span: self.eof_span(),
+            },
+        }
+    }
+
+    fn eof_span(&self) -> Span {
+        Span::new(self.end, self.end)
+    }
+
+    // span of the keyword starting the current item
+    fn keyword(&mut self) -> Span {
+        match self.bump() {
+            Some(lexeme) => lexeme.span,
+            None => self.eof_span(),
+        }
+    }
+
+    // `parse` one level further in
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        self.chain(|this| {
+            this.link()?;
+            parse(this)
+        })
+    }
+
+    // `parse` a chain of operators, every `link` in it goes one level
+    // further in until the whole chain is done
+    fn chain<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        let nesting = self.nesting;
+        let result = parse(self);
+        self.nesting = nesting;
+
+        result
+    }
+
+    // one more level of nesting, the operators in `1 + 1 + 1` or `x.a.b`
+    // nest the tree as deeply as brackets do
+    fn link(&mut self) -> Result<(), ParseError> {
+        if self.nesting >= MAX_NESTING {
+            let span = match self.peek() {
+                Some(lexeme) => lexeme.span,
+                None => self.eof_span(),
+            };
+
+            return Err(ParseError::NestedTooDeeply { span });
+        }
+        self.nesting += 1;
+
+        Ok(())
+    }
 
-                    assert_eq!(peekable.next(), Some(&Lexeme::ByteToken(Token::CloseParen)));
+    // skip past the rest of a broken item that started at `item_depth`,
+    // stopping after the `}` closing it, however deep in its body the error
+    // was. Outside of any braces a `;` ends the item too, and an item
+    // keyword starts the next one
+    fn synchronize(&mut self, item_depth: usize) {
+        loop {
+            let depth = self.depth;
+            let outside = depth <= item_depth;
+            let Some(lexeme) = self.peek() else {
+                return;
+            };
 
-                    tree.push(Ast::FunctionCall(DynaCall {
-                        fn_name: flush_whitespace(token),
-                        signature: types
-                    }))
+            match &lexeme.kind {
+                LexemeKind::VariableByteToken(VarToken::Fn | VarToken::Struct | VarToken::Enum)
+                    if outside =>
+                {
+                    return
+                }
+                LexemeKind::ByteToken(Token::CloseCurly) if depth <= item_depth + 1 => {
+                    self.bump();
+                    return;
                 }
+                LexemeKind::ByteToken(Token::Semicolon) if outside => {
+                    self.bump();
+                    return;
+                }
+                _ => (),
             }
-            _ => {
-                peekable.next();
+
+            self.bump();
+        }
+    }
+
+    // `fn name(arg: Type, mut other: Type) -> Type { ... }`
+    fn function(&mut self, docs: Vec<String>) -> Result<Ast<'src>, ParseError> {
+        let start = self.keyword();
+        let (name, _) = self.expect_identifier(Expected::Name)?;
+
+        // the generics are in scope for the rest of the function
+        let generics = self.generics()?;
+        self.generics.clone_from(&generics);
+        let function = self.function_rest(docs, start, name, generics);
+        self.generics.clear();
+
+        function
+    }
+
+    // the parameters, return type and body of a function
+    fn function_rest(
+        &mut self,
+        docs: Vec<String>,
+        start: Span,
+        name: Symbol,
+        generics: Vec<Symbol>,
+    ) -> Result<Ast<'src>, ParseError> {
+        let mut params = Vec::new();
+
+        self.expect(Token::OpenParen)?;
+        while !self.at_end_of(Token::CloseParen) {
+            params.push(self.param()?);
+
+            if self.eat(Token::Comma).is_none() {
+                break;
+            }
+        }
+        self.expect(Token::CloseParen)?;
+
+        let return_type = match self.eat(Token::Arrow) {
+            Some(_) => self.expect_type()?,
+            None => Type::Unit,
+        };
+
+        let body = self.block()?;
+        let span = start.to(body.span);
+
+        Ok(Ast::Function(DynaFunction {
+            docs,
+            name,
+            generics,
+            body,
+            params,
+            return_type,
+            span,
+        }))
+    }
+
+    // `<T, U>` after a function's name, if there is one
+    fn generics(&mut self) -> Result<Vec<Symbol>, ParseError> {
+        let mut generics = Vec::new();
+        if self.eat(Token::Lt).is_none() {
+            return Ok(generics);
+        }
+
+        while !self.at_end_of(Token::Gt) {
+            generics.push(self.expect_identifier(Expected::Name)?.0);
+
+            if self.eat(Token::Comma).is_none() {
+                break;
             }
         }
+        self.expect(Token::Gt)?;
+
+        Ok(generics)
     }
 
-    tree
-}
+    // `{ stmt; stmt; tail }`
+    fn block(&mut self) -> Result<Block<'src>, ParseError> {
+        let open = self.expect(Token::OpenCurly)?;
+        let mut stmts = Vec::new();
+        let mut tail = None;
+
+        let close = loop {
+            if let Some(close) = self.eat(Token::CloseCurly) {
+                break close;
+            }
+            if self.eat(Token::Semicolon).is_some() {
+                continue;
+            }
+            if let Some(start) = self.eat_keyword(VarToken::Let) {
+                stmts.push(self.let_stmt(start)?);
+                continue;
+            }
+
+            // a statement starting with a block, `if`, `match` or loop ends
+            // at its `}`, so `if c { ... } -x` is the `if` and then `-x`
+            // rather than a subtraction
+            if self.at_block_like() {
+                let expr = self.nested(Self::primary)?;
+
+                if self.eat(Token::Semicolon).is_some() || !self.at_end_of(Token::CloseCurly) {
+                    stmts.push(Stmt::Expr(expr));
+                } else {
+                    tail = Some(Box::new(expr));
+                }
+                continue;
+            }
+
+            let expr = self.expr()?;
+
+            if self.eat(Token::Semicolon).is_some() {
+                stmts.push(Stmt::Expr(expr));
+            } else if self.at_end_of(Token::CloseCurly) {
+                tail = Some(Box::new(expr));
+            } else {
+                return Err(self.unexpected(Expected::Token(Token::Semicolon)));
+            }
+        };
+
+        Ok(Block {
+            stmts,
+            tail,
+            span: open.to(close),
+        })
+    }
+
+    // `let pattern: Type = init;` after the `let`
+    fn let_stmt(&mut self, start: Span) -> Result<Stmt<'src>, ParseError> {
+        let pattern = self.pattern()?;
+        let let_type = match self.eat(Token::Colon) {
+            Some(_) => Some(self.expect_type()?),
+            None => None,
+        };
+
+        self.expect(Token::Eq)?;
+        let init = self.expr()?;
+        let end = self.expect(Token::Semicolon)?;
+
+        Ok(Stmt::Let {
+            pattern,
+            let_type,
+            init,
+            span: start.to(end),
+        })
+    }
+
+    // a pattern, or several separated by `|`
+    fn pattern(&mut self) -> Result<Pattern<'src>, ParseError> {
+        let first = self.nested(Self::single_pattern)?;
+        let mut span = first.span();
+        let mut alternatives = vec![first];
+
+        while self.eat(Token::Pipe).is_some() {
+            let alternative = self.nested(Self::single_pattern)?;
+            span = span.to(alternative.span());
+            alternatives.push(alternative);
+        }
+
+        if alternatives.len() == 1 {
+            return Ok(alternatives.remove(0));
+        }
+
+        Ok(Pattern::Or { alternatives, span })
+    }
+
+    // any pattern other than an or-pattern
+    fn single_pattern(&mut self) -> Result<Pattern<'src>, ParseError> {
+        if let Some(start) = self.eat_keyword(VarToken::Mut) {
+            let (name, span) = self.expect_identifier(Expected::Name)?;
+
+            return Ok(Pattern::Binding {
+                name,
+                mutable: true,
+                span: start.to(span),
+            });
+        }
+
+        let Some(lexeme) = self.peek() else {
+            return Err(self.unexpected(Expected::Pattern));
+        };
+        let span = lexeme.span;
+
+        let value = match &lexeme.kind {
+            LexemeKind::Identifier(_) => return self.path_pattern(),
+            LexemeKind::Integer(integer) => Literal::Int(integer.clone()),
+            LexemeKind::Float(float) => Literal::Float(float.clone()),
+            LexemeKind::Str(string) => Literal::Str(string.clone()),
+            LexemeKind::Char(racter) => Literal::Char(*racter),
+            LexemeKind::VariableByteToken(VarToken::True) => Literal::Bool(true),
+            LexemeKind::VariableByteToken(VarToken::False) => Literal::Bool(false),
+            // `let loop = 1;`, any other keyword would be a binding
+            LexemeKind::VariableByteToken(keyword) => {
+                return Err(ParseError::ReservedWord {
+                    keyword: keyword.clone(),
+                    span,
+                })
+            }
+            _ => return Err(self.unexpected(Expected::Pattern)),
+        };
+        self.bump();
+
+        Ok(Pattern::Literal { value, span })
+    }
+
+    // `_`, `name`, `Enum::Variant`, `Enum::Variant(a, b)` or
+    // `Struct { field, .. }`
+    fn path_pattern(&mut self) -> Result<Pattern<'src>, ParseError> {
+        let Expr::Path { segments, span } = self.path()? else {
+            unreachable!("`path` only makes paths");
+        };
+
+        if self.eat(Token::OpenParen).is_some() {
+            let mut elements = Vec::new();
+
+            while !self.at_end_of(Token::CloseParen) {
+                elements.push(self.pattern()?);
+
+                if self.eat(Token::Comma).is_none() {
+                    break;
+                }
+            }
+            let close = self.expect(Token::CloseParen)?;
+
+            return Ok(Pattern::TupleStruct {
+                path: segments,
+                elements,
+                span: span.to(close),
+            });
+        }
+
+        if self.eat(Token::OpenCurly).is_some() {
+            let mut fields = Vec::new();
+            let mut rest = false;
+
+            while !self.at_end_of(Token::CloseCurly) {
+                // `..` has to come last
+                if self.eat(Token::DotDot).is_some() {
+                    rest = true;
+                    break;
+                }
+                fields.push(self.field_pattern()?);
+
+                if self.eat(Token::Comma).is_none() {
+                    break;
+                }
+            }
+            let close = self.expect(Token::CloseCurly)?;
+
+            return Ok(Pattern::Struct {
+                path: segments,
+                fields,
+                rest,
+                span: span.to(close),
+            });
+        }
+
+        match segments[..] {
+            [name] if name.as_str() == "_" => Ok(Pattern::Wildcard { span }),
+            [name] => Ok(Pattern::Binding {
+                name,
+                mutable: false,
+                span,
+            }),
+            _ => Ok(Pattern::Path { segments, span }),
+        }
+    }
+
+    // `field: pattern`, `field` or `mut field`
+    fn field_pattern(&mut self) -> Result<FieldPattern<'src>, ParseError> {
+        let mutable = self.eat_keyword(VarToken::Mut);
+        let (name, span) = self.expect_identifier(Expected::Name)?;
+
+        if mutable.is_none() && self.eat(Token::Colon).is_some() {
+            let pattern = self.pattern()?;
+
+            return Ok(FieldPattern {
+                name,
+                span: span.to(pattern.span()),
+                pattern,
+            });
+        }
+
+        let span = mutable.unwrap_or(span).to(span);
+
+        Ok(FieldPattern {
+            name,
+            pattern: Pattern::Binding {
+                name,
+                mutable: mutable.is_some(),
+                span,
+            },
+            span,
+        })
+    }
+
+    fn expr(&mut self) -> Result<Expr<'src>, ParseError> {
+        self.restricted(false)
+    }
+
+    // `if x == Point { .. }` can't be a struct literal, it would swallow the
+    // block, wrapping it in parentheses still works
+    fn condition(&mut self) -> Result<Expr<'src>, ParseError> {
+        self.restricted(true)
+    }
+
+    // any expression nested inside brackets of some kind goes back through
+    // `expr` and can have struct literals again
+    fn restricted(&mut self, no_struct_literal: bool) -> Result<Expr<'src>, ParseError> {
+        let outer = std::mem::replace(&mut self.no_struct_literal, no_struct_literal);
+        let expr = self.nested(Self::assignment);
+        self.no_struct_literal = outer;
+
+        expr
+    }
+
+    // assignment is the loosest operator and the only right associative one,
+    // `a = b = c` is `a = (b = c)`
+    fn assignment(&mut self) -> Result<Expr<'src>, ParseError> {
+        let target = self.range()?;
+
+        let op = match self.peek().map(|lexeme| &lexeme.kind) {
+            Some(LexemeKind::ByteToken(Token::Eq)) => None,
+            Some(LexemeKind::ByteToken(Token::PlusEq)) => Some(BinaryOp::Add),
+            Some(LexemeKind::ByteToken(Token::MinusEq)) => Some(BinaryOp::Sub),
+            Some(LexemeKind::ByteToken(Token::StarEq)) => Some(BinaryOp::Mul),
+            Some(LexemeKind::ByteToken(Token::SlashEq)) => Some(BinaryOp::Div),
+            Some(LexemeKind::ByteToken(Token::PercentEq)) => Some(BinaryOp::Rem),
+            _ => return Ok(target),
+        };
+        self.bump();
+        let value = self.nested(Self::assignment)?;
+
+        Ok(Expr::Assign {
+            op,
+            span: target.span().to(value.span()),
+            target: Box::new(target),
+            value: Box::new(value),
+        })
+    }
+
+    // `start..end` binds looser than any binary operator, `0..n + 1` is
+    // `0..(n + 1)`, and ranges don't chain
+    fn range(&mut self) -> Result<Expr<'src>, ParseError> {
+        let start = match self.at(Token::DotDot) || self.at(Token::DotDotEq) {
+            true => None,
+            false => Some(self.expr_with_power(0)?),
+        };
+        let inclusive = match self.peek().map(|lexeme| &lexeme.kind) {
+            Some(LexemeKind::ByteToken(Token::DotDot)) => false,
+            Some(LexemeKind::ByteToken(Token::DotDotEq)) => true,
+            _ => return start.ok_or_else(|| self.unexpected(Expected::Expression)),
+        };
+        let op = self.keyword();
+
+        // `start..=` has nowhere to stop
+        let end = match !inclusive && self.at_expression_end() {
+            true => None,
+            false => Some(self.expr_with_power(0)?),
+        };
+        let first = start.as_ref().map_or(op, |start| start.span());
+        let last = end.as_ref().map_or(op, |end| end.span());
+
+        Ok(Expr::Range {
+            start: start.map(Box::new),
+            end: end.map(Box::new),
+            inclusive,
+            span: first.to(last),
+        })
+    }
+
+    // precedence climbing, binary operators that bind less tightly than
+    // `min_power` are left for the caller
+    fn expr_with_power(&mut self, min_power: u8) -> Result<Expr<'src>, ParseError> {
+        self.chain(|this| {
+            let mut lhs = this.unary()?;
+            let mut compared = false;
+
+            loop {
+                if CAST_POWER >= min_power && this.eat_keyword(VarToken::As).is_some() {
+                    this.link()?;
+                    let cast_type = this.expect_type()?;
+
+                    lhs = Expr::Cast {
+                        span: lhs.span().to(this.prev_span),
+                        value: Box::new(lhs),
+                        cast_type,
+                    };
+                    continue;
+                }
+
+                let Some(op) = this.peek().and_then(|lexeme| match &lexeme.kind {
+                    LexemeKind::ByteToken(token) => BinaryOp::from_token(token),
+                    _ => None,
+                }) else {
+                    break;
+                };
+                let (left_power, right_power) = op.binding_power();
+                if left_power < min_power {
+                    break;
+                }
+
+                this.link()?;
+                let op_span = this.keyword();
+                if op.is_comparison() && compared {
+                    return Err(ParseError::ChainedComparison {
+                        span: lhs.span().to(op_span),
+                    });
+                }
+                compared = op.is_comparison();
+
+                let rhs = this.expr_with_power(right_power)?;
+                lhs = Expr::Binary {
+                    op,
+                    span: lhs.span().to(rhs.span()),
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                };
+            }
+
+            Ok(lhs)
+        })
+    }
+
+    // `-x`, `!x` or a primary expression with its postfix operators
+    fn unary(&mut self) -> Result<Expr<'src>, ParseError> {
+        let op = match self.peek().map(|lexeme| &lexeme.kind) {
+            Some(LexemeKind::ByteToken(Token::Minus)) => UnaryOp::Neg,
+            Some(LexemeKind::ByteToken(Token::Bang)) => UnaryOp::Not,
+            _ => {
+                let primary = self.primary()?;
+                return self.postfix(primary);
+            }
+        };
+        let start = self.keyword();
+        let operand = self.nested(Self::unary)?;
+
+        Ok(Expr::Unary {
+            op,
+            span: start.to(operand.span()),
+            operand: Box::new(operand),
+        })
+    }
+
+    // calls `f(x)`, method calls `x.f()`, field access `x.f` and indexing
+    // `x[i]`, these chain left to right
+    fn postfix(&mut self, mut expr: Expr<'src>) -> Result<Expr<'src>, ParseError> {
+        self.chain(|this| loop {
+            expr = if this.eat(Token::OpenParen).is_some() {
+                this.link()?;
+                let (args, close) = this.args()?;
+
+                Expr::Call {
+                    span: expr.span().to(close),
+                    callee: Box::new(expr),
+                    args,
+                }
+            } else if this.eat(Token::Dot).is_some() {
+                this.link()?;
+                let (name, name_span) = this.expect_identifier(Expected::Name)?;
+
+                if this.eat(Token::OpenParen).is_some() {
+                    let (args, close) = this.args()?;
+
+                    Expr::MethodCall {
+                        span: expr.span().to(close),
+                        receiver: Box::new(expr),
+                        method: name,
+                        args,
+                    }
+                } else {
+                    Expr::Field {
+                        span: expr.span().to(name_span),
+                        base: Box::new(expr),
+                        field: name,
+                    }
+                }
+            } else if this.eat(Token::OpenSquare).is_some() {
+                this.link()?;
+                let index = this.expr()?;
+                let close = this.expect(Token::CloseSquare)?;
+
+                Expr::Index {
+                    span: expr.span().to(close),
+                    base: Box::new(expr),
+                    index: Box::new(index),
+                }
+            } else {
+                return Ok(expr);
+            };
+        })
+    }
+
+    // `args, ...)` after the `(` of a call
+    fn args(&mut self) -> Result<(Vec<Expr<'src>>, Span), ParseError> {
+        let mut args = Vec::new();
+
+        while !self.at_end_of(Token::CloseParen) {
+            args.push(self.expr()?);
+
+            if self.eat(Token::Comma).is_none() {
+                break;
+            }
+        }
+        let close = self.expect(Token::CloseParen)?;
+
+        Ok((args, close))
+    }
+
+    // literals, paths, struct literals, arrays, blocks, `if`, `match`,
+    // loops, `break`, `continue`, `return` and `(expr)`
+    fn primary(&mut self) -> Result<Expr<'src>, ParseError> {
+        let Some(lexeme) = self.peek() else {
+            return Err(self.unexpected(Expected::Expression));
+        };
+        let span = lexeme.span;
+
+        let value = match &lexeme.kind {
+            LexemeKind::ByteToken(Token::OpenCurly) => return self.block().map(Expr::Block),
+            LexemeKind::Identifier(_) => {
+                let path = self.path()?;
+
+                if self.no_struct_literal || !self.at(Token::OpenCurly) {
+                    return Ok(path);
+                }
+
+                return self.struct_literal(path);
+            }
+            LexemeKind::VariableByteToken(VarToken::If) => return self.if_expr(),
+            LexemeKind::VariableByteToken(VarToken::Match) => return self.match_expr(),
+            LexemeKind::VariableByteToken(VarToken::While | VarToken::Loop | VarToken::For) => {
+                return self.loop_expr(None);
+            }
+            LexemeKind::Label(label) => {
+                let label = *label;
+                self.bump();
+                self.expect(Token::Colon)?;
+
+                return self.loop_expr(Some((label, span)));
+            }
+            LexemeKind::VariableByteToken(VarToken::Break) => {
+                self.bump();
+                let label = self.label();
+                let value = match self.at_expression_end() {
+                    true => None,
+                    false => Some(Box::new(self.expr()?)),
+                };
+
+                return Ok(Expr::Break {
+                    label,
+                    value,
+                    span: span.to(self.prev_span),
+                });
+            }
+            LexemeKind::VariableByteToken(VarToken::Continue) => {
+                self.bump();
+                let label = self.label();
+
+                return Ok(Expr::Continue {
+                    label,
+                    span: span.to(self.prev_span),
+                });
+            }
+            LexemeKind::VariableByteToken(VarToken::Return) => {
+                self.bump();
+
+                // `return` on its own returns unit
+                let value = match self.at_expression_end() {
+                    true => None,
+                    false => Some(Box::new(self.expr()?)),
+                };
+
+                return Ok(Expr::Return {
+                    span: value.as_ref().map_or(span, |value| span.to(value.span())),
+                    value,
+                });
+            }
+            LexemeKind::ByteToken(Token::OpenParen) => {
+                self.bump();
+
+                if let Some(close) = self.eat(Token::CloseParen) {
+                    return Ok(Expr::Literal {
+                        value: Literal::Unit,
+                        span: span.to(close),
+                    });
+                }
+
+                // the parentheses only group, they don't make a node
+                let inner = self.expr()?;
+                self.expect(Token::CloseParen)?;
+
+                return Ok(inner);
+            }
+            LexemeKind::ByteToken(Token::OpenSquare) => {
+                self.bump();
+                let mut elements = Vec::new();
+
+                while !self.at_end_of(Token::CloseSquare) {
+                    let element = self.expr()?;
+
+                    // `[value; len]`
+                    if elements.is_empty() && self.eat(Token::Semicolon).is_some() {
+                        let len = self.expr()?;
+                        let close = self.expect(Token::CloseSquare)?;
+
+                        return Ok(Expr::Repeat {
+                            value: Box::new(element),
+                            len: Box::new(len),
+                            span: span.to(close),
+                        });
+                    }
+                    elements.push(element);
+
+                    if self.eat(Token::Comma).is_none() {
+                        break;
+                    }
+                }
+                let close = self.expect(Token::CloseSquare)?;
+
+                return Ok(Expr::Array {
+                    elements,
+                    span: span.to(close),
+                });
+            }
+            LexemeKind::Integer(integer) => Literal::Int(integer.clone()),
+            LexemeKind::Float(float) => Literal::Float(float.clone()),
+            LexemeKind::Str(string) => Literal::Str(string.clone()),
+            LexemeKind::Char(racter) => Literal::Char(*racter),
+            LexemeKind::VariableByteToken(VarToken::True) => Literal::Bool(true),
+            LexemeKind::VariableByteToken(VarToken::False) => Literal::Bool(false),
+            _ => return Err(self.unexpected(Expected::Expression)),
+        };
+        self.bump();
+
+        Ok(Expr::Literal { value, span })
+    }
+
+    // `if condition { ... }` with any number of `else if`s and an optional
+    // `else` on the end
+    fn if_expr(&mut self) -> Result<Expr<'src>, ParseError> {
+        let start = self.keyword();
+        let condition = self.condition()?;
+        let then_block = self.block()?;
+        let mut span = start.to(then_block.span);
+
+        let else_branch = match self.eat_keyword(VarToken::Else) {
+            Some(_) => {
+                let else_branch = match self.peek().map(|lexeme| &lexeme.kind) {
+                    Some(LexemeKind::VariableByteToken(VarToken::If)) => {
+                        self.nested(Self::if_expr)?
+                    }
+                    _ => Expr::Block(self.block()?),
+                };
+                span = span.to(else_branch.span());
+
+                Some(Box::new(else_branch))
+            }
+            None => None,
+        };
+
+        Ok(Expr::If {
+            condition: Box::new(condition),
+            then_block,
+            else_branch,
+            span,
+        })
+    }
+
+    // `match scrutinee { pattern if guard => body, ... }`, the `,` after an
+    // arm can be left out when its body ends in a `}`
+    fn match_expr(&mut self) -> Result<Expr<'src>, ParseError> {
+        let start = self.keyword();
+        let scrutinee = self.condition()?;
+        self.expect(Token::OpenCurly)?;
+        let mut arms = Vec::new();
+
+        let close = loop {
+            if let Some(close) = self.eat(Token::CloseCurly) {
+                break close;
+            }
+
+            let pattern = self.pattern()?;
+            let guard = match self.eat_keyword(VarToken::If) {
+                Some(_) => Some(self.expr()?),
+                None => None,
+            };
+            self.expect(Token::FatArrow)?;
+            let body = self.expr()?;
+
+            if self.eat(Token::Comma).is_none()
+                && !body.is_block_like()
+                && !self.at_end_of(Token::CloseCurly)
+            {
+                return Err(self.unexpected(Expected::Token(Token::Comma)));
+            }
+
+            arms.push(MatchArm {
+                span: pattern.span().to(body.span()),
+                pattern,
+                guard,
+                body,
+            });
+        };
+
+        Ok(Expr::Match {
+            scrutinee: Box::new(scrutinee),
+            arms,
+            span: start.to(close),
+        })
+    }
+
+    // `Path { field: value, field, ..base }` after the path
+    fn struct_literal(&mut self, path: Expr<'src>) -> Result<Expr<'src>, ParseError> {
+        let Expr::Path { segments, span } = path else {
+            unreachable!("struct literals only follow paths");
+        };
+        self.expect(Token::OpenCurly)?;
+        let mut fields = Vec::new();
+        let mut base = None;
+
+        while !self.at_end_of(Token::CloseCurly) {
+            // `..base` has to come last
+            if self.eat(Token::DotDot).is_some() {
+                base = Some(Box::new(self.expr()?));
+                break;
+            }
+            fields.push(self.field_init()?);
+
+            if self.eat(Token::Comma).is_none() {
+                break;
+            }
+        }
+        let close = self.expect(Token::CloseCurly)?;
+
+        Ok(Expr::Struct {
+            path: segments,
+            fields,
+            base,
+            span: span.to(close),
+        })
+    }
+
+    // `field: value` or `field` on its own for `field: field`
+    fn field_init(&mut self) -> Result<FieldInit<'src>, ParseError> {
+        let (name, span) = self.expect_identifier(Expected::Name)?;
+
+        let value = match self.eat(Token::Colon) {
+            Some(_) => self.expr()?,
+            None => Expr::Path {
+                segments: vec![name],
+                span,
+            },
+        };
+
+        Ok(FieldInit {
+            name,
+            span: span.to(value.span()),
+            value,
+        })
+    }
+
+    // `while`, `loop` or `for`, `label` is the `'label:` in front of it
+    fn loop_expr(&mut self, label: Option<(Symbol, Span)>) -> Result<Expr<'src>, ParseError> {
+        let keyword = match self.peek().map(|lexeme| &lexeme.kind) {
+            Some(LexemeKind::VariableByteToken(
+                keyword @ (VarToken::While | VarToken::Loop | VarToken::For),
+            )) => keyword.clone(),
+            _ => return Err(self.unexpected(Expected::Loop)),
+        };
+        let start = self.keyword();
+        let (label, start) = match label {
+            Some((label, label_span)) => (Some(label), label_span),
+            None => (None, start),
+        };
+
+        let expr = match keyword {
+            VarToken::While => {
+                let condition = self.condition()?;
+                let body = self.block()?;
+
+                Expr::While {
+                    label,
+                    condition: Box::new(condition),
+                    span: start.to(body.span),
+                    body,
+                }
+            }
+            VarToken::For => {
+                let pattern = self.pattern()?;
+                if self.eat_keyword(VarToken::In).is_none() {
+                    return Err(self.unexpected(Expected::Keyword(VarToken::In)));
+                }
+                let iterable = self.condition()?;
+                let body = self.block()?;
+
+                Expr::For {
+                    label,
+                    pattern,
+                    iterable: Box::new(iterable),
+                    span: start.to(body.span),
+                    body,
+                }
+            }
+            _ => {
+                let body = self.block()?;
+
+                Expr::Loop {
+                    label,
+                    span: start.to(body.span),
+                    body,
+                }
+            }
+        };
+
+        Ok(expr)
+    }
+
+    // the `'label` after `break` or `continue`
+    fn label(&mut self) -> Option<Symbol> {
+        let label = match self.peek()?.kind {
+            LexemeKind::Label(label) => label,
+            _ => return None,
+        };
+        self.bump();
+
+        Some(label)
+    }
+
+    // whether the expression that could come next is left out, like the
+    // value of `return` or the end of `start..`
+    fn at_expression_end(&mut self) -> bool {
+        let no_struct_literal = self.no_struct_literal;
+
+        match self.peek().map(|lexeme| &lexeme.kind) {
+            None
+            | Some(LexemeKind::ByteToken(
+                Token::Semicolon
+                | Token::CloseCurly
+                | Token::CloseParen
+                | Token::CloseSquare
+                | Token::Comma
+                | Token::FatArrow,
+            )) => true,
+            // `for i in 0.. { ... }`
+            Some(LexemeKind::ByteToken(Token::OpenCurly)) => no_struct_literal,
+            _ => false,
+        }
+    }
+
+    // `name` or `Enum::Variant`
+    fn path(&mut self) -> Result<Expr<'src>, ParseError> {
+        let (first, start) = self.expect_identifier(Expected::Name)?;
+        let mut segments = vec![first];
+        let mut span = start;
+
+        while self.eat(Token::DoubleColon).is_some() {
+            let (segment, end) = self.expect_identifier(Expected::Name)?;
+            segments.push(segment);
+            span = start.to(end);
+        }
+
+        Ok(Expr::Path { segments, span })
+    }
+
+    // `mut name: Type`
+    fn param(&mut self) -> Result<Param, ParseError> {
+        let mutable = self.eat_keyword(VarToken::Mut);
+        let (name, span) = self.expect_identifier(Expected::Name)?;
+        self.expect(Token::Colon)?;
+        let param_type = self.expect_type()?;
+
+        Ok(Param {
+            name,
+            param_type,
+            mutable: mutable.is_some(),
+            span: mutable.unwrap_or(span).to(span),
+        })
+    }
+
+    // `struct Name { field: Type, ... }`
+    fn structure(&mut self, docs: Vec<String>) -> Result<Ast<'src>, ParseError> {
+        let start = self.keyword();
+        let (name, _) = self.expect_identifier(Expected::Name)?;
+        let (fields, close) = self.named_fields()?;
+
+        Ok(Ast::Structure(DynaStruct {
+            docs,
+            name,
+            fields,
+            span: start.to(close),
+        }))
+    }
+
+    // `{ name: Type, ... }` of a struct or struct-like variant
+    fn named_fields(&mut self) -> Result<(Vec<(Symbol, Type)>, Span), ParseError> {
+        let mut fields = Vec::new();
+
+        self.expect(Token::OpenCurly)?;
+        while !self.at_end_of(Token::CloseCurly) {
+            let (field_name, span) = self.expect_identifier(Expected::Name)?;
+            if fields.iter().any(|(name, _)| *name == field_name) {
+                return Err(ParseError::DuplicateField {
+                    name: field_name,
+                    span,
+                });
+            }
+            self.expect(Token::Colon)?;
+            let field_type = self.expect_type()?;
+
+            fields.push((field_name, field_type));
+
+            if self.eat(Token::Comma).is_none() {
+                break;
+            }
+        }
+        let close = self.expect(Token::CloseCurly)?;
+
+        Ok((fields, close))
+    }
+
+    // `enum Name { Variant, Variant(Type, ...), Variant { field: Type }, ... }`
+    fn enumeration(&mut self, docs: Vec<String>) -> Result<Ast<'src>, ParseError> {
+        let start = self.keyword();
+        let (name, _) = self.expect_identifier(Expected::Name)?;
+        let mut variants: Vec<Variant> = Vec::new();
+
+        self.expect(Token::OpenCurly)?;
+        while !self.at_end_of(Token::CloseCurly) {
+            let variant = self.variant()?;
+            if variants.iter().any(|other| other.name == variant.name) {
+                let start = variant.span.start;
+
+                return Err(ParseError::DuplicateVariant {
+                    name: variant.name,
+                    span: Span::new(start, start + variant.name.as_str().len()),
+                });
+            }
+            variants.push(variant);
+
+            if self.eat(Token::Comma).is_none() {
+                break;
+            }
+        }
+        let close = self.expect(Token::CloseCurly)?;
+
+        Ok(Ast::Enumeration(DynaEnum {
+            docs,
+            name,
+            variants,
+            span: start.to(close),
+        }))
+    }
+
+    // `Name`, `Name(Type, ...)` or `Name { field: Type, ... }`
+    fn variant(&mut self) -> Result<Variant, ParseError> {
+        let (name, start) = self.expect_identifier(Expected::Name)?;
+
+        let fields = if self.eat(Token::OpenParen).is_some() {
+            let mut types = Vec::new();
+
+            while !self.at_end_of(Token::CloseParen) {
+                types.push(self.expect_type()?);
+
+                if self.eat(Token::Comma).is_none() {
+                    break;
+                }
+            }
+            self.expect(Token::CloseParen)?;
+
+            VariantFields::Tuple(types)
+        } else if self.at(Token::OpenCurly) {
+            VariantFields::Struct(self.named_fields()?.0)
+        } else {
+            VariantFields::Unit
+        };
+
+        Ok(Variant {
+            name,
+            fields,
+            span: start.to(self.prev_span),
+        })
+    }
+
+    // `name(Type, Type)`, a name not followed by `(` isn't an item
+    fn call(&mut self) -> Result<Ast<'src>, ParseError> {
+        let (fn_name, start) = self.expect_identifier(Expected::Name)?;
+
+        if self.eat(Token::OpenParen).is_none() {
+            return Err(ParseError::Expected {
+                expected: Expected::Item,
+                found: format!("`{}`", fn_name),
+                span: start,
+            });
+        }
+
+        let mut signature = Vec::new();
+        while !self.at_end_of(Token::CloseParen) {
+            signature.push(self.expect_type()?);
+
+            if self.eat(Token::Comma).is_none() {
+                break;
+            }
+        }
+        let close = self.expect(Token::CloseParen)?;
+
+        Ok(Ast::FunctionCall(DynaCall {
+            fn_name,
+            signature,
+            span: start.to(close),
+        }))
+    }
+}
+
+// the first item of `code`, which has to parse and be a function
+#[cfg(test)]
+fn function(code: &str) -> DynaFunction<'_> {
+    use crate::lexer::Lexer;
+
+    match tree(Lexer::new(code)).unwrap().items.into_iter().next() {
+        Some(Ast::Function(function)) => function,
+        item => panic!("expected a function, found {:?}", item),
+    }
+}
+
+// the tail expression of the first function in `code`
+#[cfg(test)]
+fn tail(code: &str) -> Expr<'_> {
+    *function(code).body.tail.unwrap()
+}
+
+#[test]
+fn test_parse_items() {
+    use crate::lexer::Lexer;
+
+    let code = "fn main(arg: String) { arg }
+    struct X { value: Type, another: String, }
+    enum Option { Some(String), None, }
+    main(String)";
+
+    let program = tree(Lexer::new(code)).unwrap();
+
+    assert_eq!(program.items.len(), 4);
+    match &program.items[1] {
+        Ast::Structure(structure) => {
+            let names: Vec<_> = structure
+                .fields
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect();
+
+            assert_eq!(names, ["value", "another"]);
+            assert_eq!(
+                structure.field(Symbol::intern("another")),
+                Some(&Type::named("String"))
+            );
+        }
+        item => panic!("expected a struct, found {:?}", item),
+    }
+    match &program.items[2] {
+        Ast::Enumeration(enumeration) => {
+            assert_eq!(enumeration.name.as_str(), "Option");
+            let names: Vec<_> = enumeration
+                .variants
+                .iter()
+                .map(|variant| variant.name.as_str())
+                .collect();
+
+            assert_eq!(names, ["Some", "None"]);
+            assert_eq!(
+                enumeration.variant(Symbol::intern("Some")).unwrap().fields,
+                VariantFields::Tuple(vec![Type::named("String")])
+            );
+            assert_eq!(
+                &code[enumeration.span.start..enumeration.span.end],
+                "enum Option { Some(String), None, }"
+            );
+        }
+        item => panic!("expected an enum, found {:?}", item),
+    }
+}
+
+#[test]
+fn test_parse_errors() {
+    use crate::lexer::Lexer;
+
+    let parse = |code: &'static str| tree(Lexer::new(code));
+
+    assert_eq!(
+        parse("fn main(arg String) {}").unwrap_err(),
+        vec![ParseError::Expected {
+            expected: Expected::Token(Token::Colon),
+            found: "`String`".to_string(),
+            span: Span::new(12, 18),
+        }]
+    );
+    assert_eq!(
+        parse("struct X { value: Type,").unwrap_err(),
+        vec![ParseError::UnexpectedEof {
+            expected: Expected::Token(Token::CloseCurly),
+            span: Span::new(23, 23),
+        }]
+    );
+    assert_eq!(
+        parse("call(,)").unwrap_err(),
+        vec![ParseError::Expected {
+            expected: Expected::Type,
+            found: "`,`".to_string(),
+            span: Span::new(5, 6),
+        }]
+    );
+
+    assert_eq!(
+        parse("struct S { a: u8, a: u16 }").unwrap_err(),
+        vec![ParseError::DuplicateField {
+            name: Symbol::intern("a"),
+            span: Span::new(18, 19),
+        }]
+    );
+    assert!(matches!(
+        parse("enum E { V { a: u8, b: u8, a: u8 } }").unwrap_err()[..],
+        [ParseError::DuplicateField { .. }]
+    ));
+    assert_eq!(
+        parse("enum E { A, B(u8), A { a: u8 } }").unwrap_err(),
+        vec![ParseError::DuplicateVariant {
+            name: Symbol::intern("A"),
+            span: Span::new(19, 20),
+        }]
+    );
+
+    // anything at the top level that doesn't start an item is an error
+    let errors = parse("let x = 5; 1 + 2; } )").unwrap_err();
+    let spans: Vec<_> = errors.iter().map(ParseError::span).collect();
+    assert!(errors.iter().all(|error| matches!(
+        error,
+        ParseError::Expected {
+            expected: Expected::Item,
+            ..
+        }
+    )));
+    assert_eq!(
+        spans,
+        [
+            Span::new(0, 3),
+            Span::new(11, 12),
+            Span::new(18, 19),
+            Span::new(20, 21)
+        ]
+    );
+    assert_eq!(
+        parse("name struct X {}").unwrap_err(),
+        vec![ParseError::Expected {
+            expected: Expected::Item,
+            found: "`name`".to_string(),
+            span: Span::new(0, 4),
+        }]
+    );
+}
+
+#[test]
+fn test_reserved_words() {
+    use crate::lexer::Lexer;
+
+    let parse = |code: &'static str| tree(Lexer::new(code));
+
+    assert_eq!(
+        parse("fn match() {}").unwrap_err(),
+        vec![ParseError::ReservedWord {
+            keyword: VarToken::Match,
+            span: Span::new(3, 8),
+        }]
+    );
+    assert_eq!(
+        parse("struct X { loop: Type }").unwrap_err(),
+        vec![ParseError::ReservedWord {
+            keyword: VarToken::Loop,
+            span: Span::new(11, 15),
+        }]
+    );
+    assert_eq!(
+        parse("fn f() { let loop = 1; }").unwrap_err(),
+        vec![ParseError::ReservedWord {
+            keyword: VarToken::Loop,
+            span: Span::new(13, 17),
+        }]
+    );
+    assert_eq!(
+        parse("fn f() { let mut match = 1; }").unwrap_err(),
+        vec![ParseError::ReservedWord {
+            keyword: VarToken::Match,
+            span: Span::new(17, 22),
+        }]
+    );
+    assert_eq!(
+        parse("fn f() { match x { S(while) => 1 } }").unwrap_err(),
+        vec![ParseError::ReservedWord {
+            keyword: VarToken::While,
+            span: Span::new(21, 26),
+        }]
+    );
+}
+
+#[test]
+fn test_recover_from_errors() {
+    use crate::lexer::Lexer;
+
+    let code = "fn broken(arg String) { arg }
+    struct X { value: Type, }
+    enum E { A(B C), D }
+    struct Y { value Type }
+    main(String)";
+
+    let (program, errors) = parse(Lexer::new(code));
+
+    let names: Vec<&str> = program
+        .items
+        .iter()
+        .map(|item| match item {
+            Ast::Structure(structure) => structure.name.as_str(),
+            Ast::FunctionCall(call) => call.fn_name.as_str(),
+            item => panic!("unexpected item {:?}", item),
+        })
+        .collect();
+
+    assert_eq!(names, vec!["X", "main"]);
+    assert_eq!(errors.len(), 3);
+
+    // an error deep in a body skips the whole function, the calls after it
+    // aren't taken for top level items
+    let code = "fn f() { if a { let x = ; foo(x); } bar(); } fn g() {}";
+    let (program, errors) = parse(Lexer::new(code));
+
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(
+        &program.items[..],
+        [Ast::Function(function)] if function.name.as_str() == "g"
+    ));
+}
+
+#[test]
+fn test_no_panic_on_any_input() {
+    use crate::lexer::Lexer;
+
+    let code = b"fn main(arg: String) { x } struct X { a: B, } enum E { A(B), C } f(T)";
+
+    // every truncation of a valid program and every single byte
+    for end in 0..code.len() {
+        let _ = parse(Lexer::from_bytes(&code[..end], 0));
+    }
+    for byte in 0..=u8::MAX {
+        let _ = parse(Lexer::from_bytes(&[b'f', b'n', byte, b'(', byte, b')'], 0));
+    }
+}
+
+#[test]
+fn test_nesting_limit() {
+    use crate::check::type_and_fn_call_check;
+    use crate::lexer::Lexer;
+
+    let n = 100_000;
+    let codes = [
+        format!("fn f() {{ {}x{} }}", "(".repeat(n), ")".repeat(n)),
+        format!("fn f() {}{}", "{".repeat(n), "}".repeat(n)),
+        format!("fn f() {{ {}x }}", "-".repeat(n)),
+        format!("fn f() {{ {}x }}", "a = ".repeat(n)),
+        format!("fn f() {{ if a {{}}{} }}", " else if a {}".repeat(n)),
+        format!("fn f(a: {}u8) {{}}", "&".repeat(n)),
+        format!("fn f() {{ let {}x = 1; }}", "S(".repeat(n)),
+        format!("fn f() -> u8 {{ 1{} }}", "+1".repeat(n)),
+        format!("fn f() {{ x{} }}", ".a()".repeat(n)),
+        format!("fn f() {{ x{} }}", ".a".repeat(n)),
+        format!("fn f() {{ x{} }}", "(1)".repeat(n)),
+        format!("fn f() {{ x{} }}", "[0]".repeat(n)),
+        format!("fn f() {{ 1{} }}", " as u8".repeat(n)),
+    ];
+
+    for code in &codes {
+        let (program, errors) = parse(Lexer::new(code));
+
+        assert!(errors
+            .iter()
+            .any(|error| matches!(error, ParseError::NestedTooDeeply { .. })));
+        // and whatever did parse is shallow enough to check
+        let _ = type_and_fn_call_check(&program.items);
+    }
+
+    // anything short of the limit is fine
+    let depth = MAX_NESTING - 1;
+    let code = format!("fn f() {{ {}x{} }}", "(".repeat(depth), ")".repeat(depth));
+    assert!(tree(Lexer::new(&code)).is_ok());
+    let code = format!("fn f() -> u8 {{ 1{} }}", "+1".repeat(MAX_NESTING / 2));
+    assert!(type_and_fn_call_check(&tree(Lexer::new(&code)).unwrap().items).is_ok());
+}
+
+#[test]
+fn test_doc_comments() {
+    use crate::lexer::Lexer;
+
+    let code = "//! a file
+    /// a function
+    /// over two lines
+    fn main(arg: String) { /// skipped
+        arg
+    }
+    struct X {
+        /// field docs are skipped
+        value: Type,
+    }
+    /// dropped, calls aren't items
+    main(String)
+    enum E {}";
+
+    let program = tree(Lexer::new(code)).unwrap();
+
+    assert_eq!(program.docs, vec![" a file"]);
+    let docs: Vec<&[String]> = program
+        .items
+        .iter()
+        .map(|item| match item {
+            Ast::Function(function) => function.docs.as_slice(),
+            Ast::Structure(structure) => structure.docs.as_slice(),
+            Ast::Enumeration(enumeration) => enumeration.docs.as_slice(),
+            Ast::FunctionCall(_) => &[],
+        })
+        .collect();
+
+    assert_eq!(
+        docs,
+        vec![
+            &[" a function".to_string(), " over two lines".to_string()][..],
+            &[],
+            &[],
+            &[]
+        ]
+    );
+}
+
+#[test]
+fn test_params() {
+    use crate::lexer::Lexer;
+
+    let program = tree(Lexer::new("fn f(mut a: String, b: u8,) {} fn g() {}")).unwrap();
+    let params: Vec<Vec<(&str, Type, bool)>> = program
+        .items
+        .iter()
+        .map(|item| match item {
+            Ast::Function(function) => function
+                .params
+                .iter()
+                .map(|param| (param.name.as_str(), param.param_type.clone(), param.mutable))
+                .collect(),
+            item => panic!("expected a function, found {:?}", item),
+        })
+        .collect();
+
+    assert_eq!(
+        params,
+        vec![
+            vec![
+                ("a", Type::named("String"), true),
+                ("b", Type::named("u8"), false)
+            ],
+            vec![]
+        ]
+    );
+    assert!(tree(Lexer::new("fn f(a: String b: u8) {}")).is_err());
+}
+
+#[test]
+fn test_return_types() {
+    use crate::lexer::Lexer;
+
+    let program = tree(Lexer::new("fn f() -> String {} fn g() {} fn h() -> () {}")).unwrap();
+    let return_types: Vec<Type> = program
+        .items
+        .iter()
+        .map(|item| match item {
+            Ast::Function(function) => function.return_type.clone(),
+            item => panic!("expected a function, found {:?}", item),
+        })
+        .collect();
+
+    assert_eq!(
+        return_types,
+        vec![Type::named("String"), Type::Unit, Type::Unit]
+    );
+}
+
+#[test]
+fn test_blocks() {
+    use crate::lexer::Lexer;
+
+    let code = "fn f() { g(1, \"two\"); { { h() } } return; Enum::Variant }";
+    let function = function(code);
+    let body = &function.body;
+
+    assert_eq!(body.stmts.len(), 3);
+    assert!(matches!(&body.stmts[0], Stmt::Expr(Expr::Call { args, .. }) if args.len() == 2));
+    // nested braces don't end the body early
+    match &body.stmts[1] {
+        Stmt::Expr(Expr::Block(block)) => {
+            assert_eq!(&code[block.span.start..block.span.end], "{ { h() } }")
+        }
+        stmt => panic!("expected a block, found {:?}", stmt),
+    }
+    assert!(matches!(
+        &body.stmts[2],
+        Stmt::Expr(Expr::Return { value: None, .. })
+    ));
+    assert!(matches!(
+        body.tail.as_deref(),
+        Some(Expr::Path { segments, .. }) if segments.len() == 2
+    ));
+    assert_eq!(function.span, Span::new(0, code.len()));
+
+    assert_eq!(
+        tree(Lexer::new("fn f() { g() h() }")).unwrap_err(),
+        vec![ParseError::Expected {
+            expected: Expected::Token(Token::Semicolon),
+            found: "`h`".to_string(),
+            span: Span::new(13, 14),
+        }]
+    );
+}
+
+#[test]
+fn test_expressions() {
+    use crate::lexer::Lexer;
+
+    // the tail of `fn f() { ... }` with the grouping made explicit
+    fn group(expr: &Expr) -> String {
+        match expr {
+            Expr::Literal { value, .. } => match value {
+                Literal::Int(integer) => integer.to_string(),
+                Literal::Str(string) => format!("{:?}", string),
+                literal => format!("{:?}", literal),
+            },
+            Expr::Path { segments, .. } => segments
+                .iter()
+                .map(|segment| segment.as_str())
+                .collect::<Vec<_>>()
+                .join("::"),
+            Expr::Unary { op, operand, .. } => format!("({:?} {})", op, group(operand)),
+            Expr::Binary { op, lhs, rhs, .. } => {
+                format!("({} {:?} {})", group(lhs), op, group(rhs))
+            }
+            Expr::Cast {
+                value, cast_type, ..
+            } => format!("({} as {})", group(value), cast_type),
+            Expr::Call { callee, args, .. } => format!(
+                "{}({})",
+                group(callee),
+                args.iter().map(group).collect::<Vec<_>>().join(", ")
+            ),
+            Expr::MethodCall {
+                receiver,
+                method,
+                args,
+                ..
+            } => format!(
+                "{}.{}({})",
+                group(receiver),
+                method,
+                args.iter().map(group).collect::<Vec<_>>().join(", ")
+            ),
+            Expr::Field { base, field, .. } => format!("{}.{}", group(base), field),
+            Expr::Index { base, index, .. } => format!("{}[{}]", group(base), group(index)),
+            expr => format!("{:?}", expr),
+        }
+    }
+    let parse = |code: &str| {
+        let code = format!("fn f() {{ {} }}", code);
+        let program = tree(Lexer::new(&code)).unwrap();
+
+        match &program.items[0] {
+            Ast::Function(function) => group(function.body.tail.as_ref().unwrap()),
+            item => panic!("expected a function, found {:?}", item),
+        }
+    };
+
+    assert_eq!(parse("1 + 2 * 3"), "(1 Add (2 Mul 3))");
+    assert_eq!(parse("1 - 2 - 3"), "((1 Sub 2) Sub 3)");
+    assert_eq!(parse("(1 - 2) * 3"), "((1 Sub 2) Mul 3)");
+    assert_eq!(
+        parse("a || b && c == d + 1"),
+        "(a Or (b And (c Eq (d Add 1))))"
+    );
+    assert_eq!(parse("-x as u8 * y"), "(((Neg x) as u8) Mul y)");
+    assert_eq!(parse("!a.b(1)[i].c"), "(Not a.b(1)[i].c)");
+    assert_eq!(parse("arg.push_str(\"value\")"), "arg.push_str(\"value\")");
+    assert_eq!(parse("String::from(x)(y)"), "String::from(x)(y)");
+
+    assert!(matches!(
+        tree(Lexer::new("fn f() { a < b < c }")).unwrap_err()[..],
+        [ParseError::ChainedComparison { .. }]
+    ));
+    assert!(tree(Lexer::new("fn f() { (a < b) < c }")).is_ok());
+    assert!(tree(Lexer::new("fn f() { 1 + }")).is_err());
+}
+
+#[test]
+fn test_let() {
+    use crate::lexer::Lexer;
+
+    let code = "fn f() { let mut y = 10; let array: &[u8] = [1, 2, 3, 4]; let _: [u8; 4] = array; y += 1; }";
+    let function = function(code);
+    let stmts = &function.body.stmts;
+
+    assert_eq!(stmts.len(), 4);
+    match &stmts[0] {
+        Stmt::Let {
+            pattern: Pattern::Binding { name, mutable, .. },
+            let_type: None,
+            span,
+            ..
+        } => {
+            assert_eq!(name.as_str(), "y");
+            assert!(*mutable);
+            assert_eq!(&code[span.start..span.end], "let mut y = 10;");
+        }
+        stmt => panic!("expected a let, found {:?}", stmt),
+    }
+    match &stmts[1] {
+        Stmt::Let {
+            let_type: Some(let_type),
+            init: Expr::Array { elements, .. },
+            ..
+        } => {
+            assert_eq!(let_type.to_string(), "&[u8]");
+            assert_eq!(elements.len(), 4);
+        }
+        stmt => panic!("expected a let, found {:?}", stmt),
+    }
+    assert!(matches!(
+        &stmts[2],
+        Stmt::Let { pattern: Pattern::Wildcard { .. }, let_type: Some(let_type), .. }
+            if let_type.to_string() == "[u8; 4]"
+    ));
+    assert!(matches!(
+        &stmts[3],
+        Stmt::Expr(Expr::Assign {
+            op: Some(BinaryOp::Add),
+            ..
+        })
+    ));
+
+    assert!(tree(Lexer::new("fn f() { let x; }")).is_err());
+    assert!(tree(Lexer::new("fn f() { let x = 1 }")).is_err());
+}
+
+#[test]
+fn test_if() {
+    use crate::lexer::Lexer;
+
+    let code = "fn f() { let variable = if true { String::new() } else { String::from(\"false\") }; if a { b() } else if c { d() } else { e() } if x {} }";
+    let function = function(code);
+    let body = &function.body;
+
+    assert_eq!(body.stmts.len(), 2);
+    assert!(matches!(
+        &body.stmts[0],
+        Stmt::Let { init: Expr::If { else_branch: Some(else_branch), .. }, .. }
+            if matches!(**else_branch, Expr::Block(_))
+    ));
+    // an `if` needs no `;` to be followed by another statement
+    match &body.stmts[1] {
+        Stmt::Expr(Expr::If {
+            else_branch: Some(else_branch),
+            span,
+            ..
+        }) => {
+            assert!(matches!(
+                &**else_branch,
+                Expr::If {
+                    else_branch: Some(_),
+                    ..
+                }
+            ));
+            assert_eq!(
+                &code[span.start..span.end],
+                "if a { b() } else if c { d() } else { e() }"
+            );
+        }
+        stmt => panic!("expected an if, found {:?}", stmt),
+    }
+    assert!(matches!(
+        body.tail.as_deref(),
+        Some(Expr::If {
+            else_branch: None,
+            ..
+        })
+    ));
+
+    // an `if` starting a statement doesn't carry on into an operator after
+    // its `}`
+    let program = tree(Lexer::new("fn f(x: i8) -> i8 { if true { g(); } -x }")).unwrap();
+    let Some(Ast::Function(after_if)) = program.items.first() else {
+        panic!("expected a function");
+    };
+    assert!(matches!(
+        &after_if.body.stmts[..],
+        [Stmt::Expr(Expr::If { .. })]
+    ));
+    assert!(matches!(
+        after_if.body.tail.as_deref(),
+        Some(Expr::Unary {
+            op: UnaryOp::Neg,
+            ..
+        })
+    ));
+    let program = tree(Lexer::new("fn f() { match x {} [1][0]; loop {} (1) }")).unwrap();
+    let Some(Ast::Function(after_match)) = program.items.first() else {
+        panic!("expected a function");
+    };
+    assert!(matches!(
+        &after_match.body.stmts[..],
+        [
+            Stmt::Expr(Expr::Match { .. }),
+            Stmt::Expr(Expr::Index { .. }),
+            Stmt::Expr(Expr::Loop { .. })
+        ]
+    ));
+    assert!(matches!(
+        after_match.body.tail.as_deref(),
+        Some(Expr::Literal { .. })
+    ));
+
+    assert!(tree(Lexer::new("fn f() { if a b }")).is_err());
+    assert!(tree(Lexer::new("fn f() { if a {} else b }")).is_err());
+}
+
+#[test]
+fn test_match() {
+    use crate::lexer::Lexer;
+
+    let code = "fn f() { match var { T::Some(string) => g(string), T::None => (), } }";
+    let function = function(code);
+    let Some(Expr::Match { arms, span, .. }) = function.body.tail.as_deref() else {
+        panic!("expected a match, found {:?}", function.body.tail);
+    };
+
+    assert_eq!(&code[span.start..span.end], &code[9..code.len() - 2]);
+    assert_eq!(arms.len(), 2);
+    match &arms[0].pattern {
+        Pattern::TupleStruct { path, elements, .. } => {
+            assert_eq!(path, &[Symbol::intern("T"), Symbol::intern("Some")]);
+            assert!(matches!(
+                elements[..],
+                [Pattern::Binding { name, mutable: false, .. }] if name.as_str() == "string"
+            ));
+        }
+        pattern => panic!("expected a variant pattern, found {:?}", pattern),
+    }
+    assert!(matches!(&arms[1].pattern, Pattern::Path { segments, .. } if segments.len() == 2));
+    assert!(matches!(
+        arms[1].body,
+        Expr::Literal {
+            value: Literal::Unit,
+            ..
+        }
+    ));
+
+    // every kind of pattern, guards and arms ending in `}` without a `,`
+    let code = "fn f() { match x { 0 | 1 if big => {} _ => a, Point { x, y: mut b, .. } => b, 'c' | \"s\" | true => c } }";
+    let expr = tail(code);
+    let Expr::Match { arms, .. } = &expr else {
+        panic!("expected a match, found {:?}", expr);
+    };
+
+    assert_eq!(arms.len(), 4);
+    assert!(matches!(
+        &arms[0],
+        MatchArm { pattern: Pattern::Or { alternatives, .. }, guard: Some(_), .. } if alternatives.len() == 2
+    ));
+    assert!(matches!(arms[1].pattern, Pattern::Wildcard { .. }));
+    match &arms[2].pattern {
+        Pattern::Struct { fields, rest, .. } => {
+            assert!(*rest);
+            assert!(
+                matches!(fields[0].pattern, Pattern::Binding { name, .. } if name.as_str() == "x")
+            );
+            assert!(matches!(
+                fields[1].pattern,
+                Pattern::Binding { name, mutable: true, .. } if name.as_str() == "b"
+            ));
+        }
+        pattern => panic!("expected a struct pattern, found {:?}", pattern),
+    }
+    assert!(matches!(
+        &arms[3].pattern,
+        Pattern::Or { alternatives, .. } if matches!(alternatives[..], [Pattern::Literal { .. }, Pattern::Literal { .. }, Pattern::Literal { .. }])
+    ));
+
+    assert!(tree(Lexer::new("fn f() { match x { a => b c => d } }")).is_err());
+    assert!(tree(Lexer::new("fn f() { match x { + => b } }")).is_err());
+    assert!(tree(Lexer::new("fn f() { match x { a b } }")).is_err());
+}
+
+#[test]
+fn test_struct_literals() {
+    use crate::lexer::Lexer;
+
+    match tail("fn f() { Point { x: 1, y, ..base }.x }") {
+        Expr::Field { base, field, .. } => {
+            assert_eq!(field.as_str(), "x");
+            let Expr::Struct {
+                path, fields, base, ..
+            } = *base
+            else {
+                panic!("expected a struct literal, found {:?}", base);
+            };
+
+            assert_eq!(path, vec![Symbol::intern("Point")]);
+            assert_eq!(fields.len(), 2);
+            assert!(
+                matches!(fields[1].value, Expr::Path { ref segments, .. } if segments[..] == [Symbol::intern("y")])
+            );
+            assert!(matches!(base.as_deref(), Some(Expr::Path { .. })));
+        }
+        expr => panic!("expected a field access, found {:?}", expr),
+    }
+
+    // the `{` after a condition opens the block, unless it's in parentheses
+    assert!(matches!(
+        tail("fn f() { if p == Point { } }"),
+        Expr::If { condition, .. } if matches!(*condition, Expr::Binary { .. })
+    ));
+    assert!(matches!(
+        tail("fn f() { match (Point { x }) { _ => 1 } }"),
+        Expr::Match { scrutinee, .. } if matches!(*scrutinee, Expr::Struct { .. })
+    ));
+    assert!(matches!(
+        tail("fn f() { if f(Point { x }) { } }"),
+        Expr::If { .. }
+    ));
+
+    assert!(tree(Lexer::new("fn f() { Point { ..base, x } }")).is_err());
+    assert!(tree(Lexer::new("fn f() { Point { x: } }")).is_err());
+}
+
+#[test]
+fn test_enum_variants() {
+    use crate::lexer::Lexer;
+
+    let code = "enum Error { Eof, ParseFailed(String, u32), Moved { x: u32, y: u32 }, }";
+    let program = tree(Lexer::new(code)).unwrap();
+
+    let Ast::Enumeration(enumeration) = &program.items[0] else {
+        panic!("expected an enum, found {:?}", program.items[0]);
+    };
+    let fields: Vec<_> = enumeration
+        .variants
+        .iter()
+        .map(|variant| &variant.fields)
+        .collect();
+
+    assert_eq!(
+        fields,
+        [
+            &VariantFields::Unit,
+            &VariantFields::Tuple(vec![Type::named("String"), Type::named("u32")]),
+            &VariantFields::Struct(vec![
+                (Symbol::intern("x"), Type::named("u32")),
+                (Symbol::intern("y"), Type::named("u32")),
+            ]),
+        ]
+    );
+    let span = enumeration.variants[1].span;
+    assert_eq!(&code[span.start..span.end], "ParseFailed(String, u32)");
+
+    assert!(tree(Lexer::new("enum E { A(String }")).is_err());
+    assert!(tree(Lexer::new("enum E { A { x } }")).is_err());
+}
+
+#[test]
+fn test_loops() {
+    use crate::lexer::Lexer;
+
+    let code = "fn f() { while i < n { i += 1; } 'outer: for x in 0..n { continue 'outer } let v = loop { break 10; }; for x in array {} }";
+    let function = function(code);
+    let stmts = &function.body.stmts;
+
+    assert_eq!(stmts.len(), 3);
+    assert!(matches!(
+        &stmts[0],
+        Stmt::Expr(Expr::While { label: None, condition, .. }) if matches!(**condition, Expr::Binary { .. })
+    ));
+    match &stmts[1] {
+        Stmt::Expr(Expr::For {
+            label: Some(label),
+            iterable,
+            body,
+            span,
+            ..
+        }) => {
+            assert_eq!(label.as_str(), "outer");
+            assert!(matches!(
+                **iterable,
+                Expr::Range {
+                    start: Some(_),
+                    end: Some(_),
+                    inclusive: false,
+                    ..
+                }
+            ));
+            assert!(matches!(
+                body.tail.as_deref(),
+                Some(Expr::Continue { label: Some(_), .. })
+            ));
+            assert!(code[span.start..span.end].starts_with("'outer: for"));
+        }
+        stmt => panic!("expected a for loop, found {:?}", stmt),
+    }
+    assert!(matches!(
+        &stmts[2],
+        Stmt::Let { init: Expr::Loop { body, .. }, .. }
+            if matches!(body.stmts[..], [Stmt::Expr(Expr::Break { label: None, value: Some(_), .. })])
+    ));
+    assert!(matches!(
+        function.body.tail.as_deref(),
+        Some(Expr::For { iterable, .. }) if matches!(**iterable, Expr::Path { .. })
+    ));
+
+    assert!(matches!(
+        tail("fn f() { a..=b + 1 }"),
+        Expr::Range { inclusive: true, end: Some(end), .. } if matches!(*end, Expr::Binary { .. })
+    ));
+    assert!(matches!(
+        tail("fn f() { for i in 0.. {} }"),
+        Expr::For { iterable, .. } if matches!(*iterable, Expr::Range { end: None, .. })
+    ));
+    assert!(matches!(
+        tail("fn f() { ..n }"),
+        Expr::Range { start: None, .. }
+    ));
+
+    assert!(tree(Lexer::new("fn f() { 'a: { } }")).is_err());
+    assert!(tree(Lexer::new("fn f() { for x 0..n {} }")).is_err());
+    assert!(tree(Lexer::new("fn f() { 0..= }")).is_err());
+}
+
+#[test]
+fn test_array_types() {
+    use crate::lexer::Lexer;
+
+    let code = "fn f(a: &mut [u8; 16], b: &[&str], c: [[u8; 2]; 3]) {}";
+    let function = function(code);
+
+    assert_eq!(
+        function.params[0].param_type,
+        Type::Ref {
+            mutable: true,
+            referent: Box::new(Type::Array {
+                element: Box::new(Type::named("u8")),
+                len: 16,
+            }),
+        }
+    );
+    let types: Vec<_> = function
+        .params
+        .iter()
+        .map(|param| param.param_type.to_string())
+        .collect();
+    assert_eq!(types, ["&mut [u8; 16]", "&[&str]", "[[u8; 2]; 3]"]);
+
+    assert!(matches!(
+        tail("fn f() { [0; 16] }"),
+        Expr::Repeat { value, len, .. }
+            if matches!(*value, Expr::Literal { .. }) && matches!(*len, Expr::Literal { .. })
+    ));
+    assert!(matches!(tail("fn f() { [] }"), Expr::Array { elements, .. } if elements.is_empty()));
+    assert!(
+        matches!(tail("fn f() { [1, 2,] }"), Expr::Array { elements, .. } if elements.len() == 2)
+    );
+
+    assert!(tree(Lexer::new("fn f(a: [u8; n]) {}")).is_err());
+    assert!(tree(Lexer::new("fn f() { [1 2] }")).is_err());
+    assert!(tree(Lexer::new("fn f() { [1, 2; 3] }")).is_err());
+}
+
+#[test]
+fn test_types() {
+    use crate::lexer::Lexer;
+
+    let code = "
+    fn f<T>(a: T, b: (u8, T), c: (u8,), d: (u8), e: &&str, f: fn(T, u8) -> !) -> & [T] {}
+    ";
+    let function = function(code);
+
+    assert_eq!(function.generics, [Symbol::intern("T")]);
+    assert_eq!(
+        function.params[0].param_type,
+        Type::Param(Symbol::intern("T"))
+    );
+    assert_eq!(function.params[3].param_type, Type::named("u8"));
+
+    let types: Vec<_> = function
+        .params
+        .iter()
+        .map(|param| param.param_type.to_string())
+        .collect();
+    assert_eq!(
+        types,
+        ["T", "(u8, T)", "(u8,)", "u8", "&&str", "fn(T, u8) -> !"]
+    );
+    assert_eq!(function.return_type.to_string(), "&[T]");
 
-fn flush_whitespace(literal: &[u8]) -> String {
-    let string = std::str::from_utf8(literal).unwrap().to_string();
+    // generic parameters are only in scope in their own function
+    let program = tree(Lexer::new("fn f<T>() {} fn g(t: T) {}")).unwrap();
+    assert!(matches!(
+        &program.items[1],
+        Ast::Function(g) if g.params[0].param_type == Type::named("T")
+    ));
 
-    string.replace(" ", "")
+    assert!(tree(Lexer::new("fn f(a: (u8 u8)) {}")).is_err());
+    assert!(tree(Lexer::new("fn f<T U>() {}")).is_err());
 }
@@ -0,0 +1,308 @@
+//! Types
+//!
+//! Types written in the source are parsed into a `Type` tree, so two types
+//! are the same when they have the same structure however they were spaced
+//! or spelled. The checker builds the same trees for the types it works out.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::symbol::Symbol;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    // `u8`, `f64`, `bool`, `char` or `str`
+    Primitive(Primitive),
+    // a struct or enum, or a type like `String` that isn't declared in the
+    // file
+    Named(Symbol),
+    // the `T` of a function declared as `fn name<T>(...)`
+    Param(Symbol),
+    // `&T` or `&mut T`
+    Ref { mutable: bool, referent: Box<Type> },
+    // `[T; N]`
+    Array { element: Box<Type>, len: u128 },
+    // `[T]`, only ever seen behind a reference as `&[T]`
+    Slice(Box<Type>),
+    // `(A, B)` or `(A,)`, the empty tuple is `Unit`
+    Tuple(Vec<Type>),
+    // `fn(A, B) -> R`
+    Fn { params: Vec<Type>, ret: Box<Type> },
+    // `()`
+    Unit,
+    // `!`, the type of expressions that never finish
+    Never,
+    // the types of integer and float literals without a suffix, they become
+    // whichever integer or float type they're used as
+    AnyInteger,
+    AnyFloat,
+    // a negated integer literal without a suffix, `-1` can only become a
+    // signed integer type
+    AnySignedInteger,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Primitive {
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    Isize,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    Usize,
+    F32,
+    F64,
+    Bool,
+    Char,
+    Str,
+}
+
+impl Primitive {
+    pub fn from_name(name: &str) -> Option<Primitive> {
+        let primitive = match name {
+            "i8" => Primitive::I8,
+            "i16" => Primitive::I16,
+            "i32" => Primitive::I32,
+            "i64" => Primitive::I64,
+            "i128" => Primitive::I128,
+            "isize" => Primitive::Isize,
+            "u8" => Primitive::U8,
+            "u16" => Primitive::U16,
+            "u32" => Primitive::U32,
+            "u64" => Primitive::U64,
+            "u128" => Primitive::U128,
+            "usize" => Primitive::Usize,
+            "f32" => Primitive::F32,
+            "f64" => Primitive::F64,
+            "bool" => Primitive::Bool,
+            "char" => Primitive::Char,
+            "str" => Primitive::Str,
+            _ => return None,
+        };
+
+        Some(primitive)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Primitive::I8 => "i8",
+            Primitive::I16 => "i16",
+            Primitive::I32 => "i32",
+            Primitive::I64 => "i64",
+            Primitive::I128 => "i128",
+            Primitive::Isize => "isize",
+            Primitive::U8 => "u8",
+            Primitive::U16 => "u16",
+            Primitive::U32 => "u32",
+            Primitive::U64 => "u64",
+            Primitive::U128 => "u128",
+            Primitive::Usize => "usize",
+            Primitive::F32 => "f32",
+            Primitive::F64 => "f64",
+            Primitive::Bool => "bool",
+            Primitive::Char => "char",
+            Primitive::Str => "str",
+        }
+    }
+
+    pub fn is_integer(self) -> bool {
+        !self.is_float() && !matches!(self, Primitive::Bool | Primitive::Char | Primitive::Str)
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, Primitive::F32 | Primitive::F64)
+    }
+
+    pub fn is_signed_integer(self) -> bool {
+        matches!(
+            self,
+            Primitive::I8
+                | Primitive::I16
+                | Primitive::I32
+                | Primitive::I64
+                | Primitive::I128
+                | Primitive::Isize
+        )
+    }
+}
+
+impl Type {
+    // the type a name stands for where no generic parameters are in scope,
+    // primitives by their name and anything else as a named type
+    pub fn named(name: &str) -> Type {
+        match Primitive::from_name(name) {
+            Some(primitive) => Type::Primitive(primitive),
+            None => Type::Named(Symbol::intern(name)),
+        }
+    }
+
+    // like `named` for a name that's already interned
+    pub fn from_symbol(name: Symbol) -> Type {
+        match Primitive::from_name(name.as_str()) {
+            Some(primitive) => Type::Primitive(primitive),
+            None => Type::Named(name),
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        match self {
+            Type::Primitive(primitive) => primitive.is_integer(),
+            Type::AnyInteger | Type::AnySignedInteger => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_signed_integer(&self) -> bool {
+        match self {
+            Type::Primitive(primitive) => primitive.is_signed_integer(),
+            Type::AnySignedInteger => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        match self {
+            Type::Primitive(primitive) => primitive.is_float(),
+            Type::AnyFloat => true,
+            _ => false,
+        }
+    }
+
+    // whether any of the generic parameters `names` appears in the type
+    pub fn has_params(&self, names: &[Symbol]) -> bool {
+        let has_params = |ty: &Type| ty.has_params(names);
+
+        match self {
+            Type::Param(name) => names.contains(name),
+            Type::Ref { referent, .. } => has_params(referent),
+            Type::Array { element, .. } | Type::Slice(element) => has_params(element),
+            Type::Tuple(elements) => elements.iter().any(has_params),
+            Type::Fn { params, ret } => params.iter().any(has_params) || has_params(ret),
+            _ => false,
+        }
+    }
+
+    // the type with the generic parameters found in `bound` replaced, the
+    // rest are left as they are
+    pub fn substitute(&self, bound: &HashMap<Symbol, Type>) -> Type {
+        let substitute = |ty: &Type| Box::new(ty.substitute(bound));
+
+        match self {
+            Type::Param(name) => bound.get(name).cloned().unwrap_or_else(|| self.clone()),
+            Type::Ref { mutable, referent } => Type::Ref {
+                mutable: *mutable,
+                referent: substitute(referent),
+            },
+            Type::Array { element, len } => Type::Array {
+                element: substitute(element),
+                len: *len,
+            },
+            Type::Slice(element) => Type::Slice(substitute(element)),
+            Type::Tuple(elements) => {
+                Type::Tuple(elements.iter().map(|ty| ty.substitute(bound)).collect())
+            }
+            Type::Fn { params, ret } => Type::Fn {
+                params: params.iter().map(|ty| ty.substitute(bound)).collect(),
+                ret: substitute(ret),
+            },
+            _ => self.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // `A, B` for tuples and parameter lists
+        let list = |f: &mut fmt::Formatter<'_>, types: &[Type]| {
+            for (i, ty) in types.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}", ty)?;
+            }
+
+            Ok(())
+        };
+
+        match self {
+            Type::Primitive(primitive) => f.write_str(primitive.as_str()),
+            Type::Named(name) | Type::Param(name) => write!(f, "{}", name),
+            Type::Ref {
+                mutable: true,
+                referent,
+            } => write!(f, "&mut {}", referent),
+            Type::Ref { referent, .. } => write!(f, "&{}", referent),
+            Type::Array { element, len } => write!(f, "[{}; {}]", element, len),
+            Type::Slice(element) => write!(f, "[{}]", element),
+            Type::Tuple(elements) => {
+                f.write_str("(")?;
+                list(f, elements)?;
+                // `(A,)` is a tuple, `(A)` is just `A`
+                if elements.len() == 1 {
+                    f.write_str(",")?;
+                }
+                f.write_str(")")
+            }
+            Type::Fn { params, ret } => {
+                f.write_str("fn(")?;
+                list(f, params)?;
+                write!(f, ") -> {}", ret)
+            }
+            Type::Unit => f.write_str("()"),
+            Type::Never => f.write_str("!"),
+            Type::AnyInteger => f.write_str("{integer}"),
+            Type::AnyFloat => f.write_str("{float}"),
+            Type::AnySignedInteger => f.write_str("{signed integer}"),
+        }
+    }
+}
+
+#[test]
+fn test_display() {
+    let u8_type = Type::named("u8");
+
+    assert_eq!(u8_type, Type::Primitive(Primitive::U8));
+    assert_eq!(Type::named("String"), Type::Named(Symbol::intern("String")));
+    assert_eq!(Type::from_symbol(Symbol::intern("u8")), u8_type);
+    assert_eq!(
+        Type::from_symbol(Symbol::intern("String")),
+        Type::named("String")
+    );
+
+    let types = [
+        Type::Ref {
+            mutable: true,
+            referent: Box::new(Type::Slice(Box::new(u8_type.clone()))),
+        },
+        Type::Tuple(vec![u8_type.clone()]),
+        Type::Tuple(vec![u8_type.clone(), Type::Never]),
+        Type::Fn {
+            params: vec![Type::Param(Symbol::intern("T"))],
+            ret: Box::new(Type::Unit),
+        },
+    ];
+    let shown: Vec<_> = types.iter().map(Type::to_string).collect();
+
+    assert_eq!(shown, ["&mut [u8]", "(u8,)", "(u8, !)", "fn(T) -> ()"]);
+}
+
+#[test]
+fn test_substitute() {
+    let t = Symbol::intern("T");
+    let slice = Type::Ref {
+        mutable: false,
+        referent: Box::new(Type::Slice(Box::new(Type::Param(t)))),
+    };
+    let bound = HashMap::from([(t, Type::named("u8"))]);
+
+    assert!(slice.has_params(&[t]));
+    assert!(!slice.has_params(&[Symbol::intern("U")]));
+    assert_eq!(slice.substitute(&bound).to_string(), "&[u8]");
+    assert!(!slice.substitute(&bound).has_params(&[t]));
+    assert_eq!(slice.substitute(&HashMap::new()), slice);
+}